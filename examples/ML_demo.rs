@@ -1,220 +1,348 @@
-use rust_graphics_engine::shader::Shader;
-use rust_graphics_engine::model_loading::model::Model;
-use rust_graphics_engine::camera::{Camera, CameraMovement};
-
-use glutin::event::{Event, WindowEvent, DeviceEvent, VirtualKeyCode, ElementState};
-use glutin::event_loop::{ControlFlow, EventLoop};
-use glutin::window::{WindowBuilder, CursorGrabMode};
-use glutin::{Api, ContextBuilder, GlRequest};
+use rust_graphics_engine::app::{self, App, AppConfig, AppContext};
+use rust_graphics_engine::assets::{AssetManager, Handle};
+use rust_graphics_engine::shader::{Shader, ShaderVariants};
+use rust_graphics_engine::program_cache::ProgramCache;
+use rust_graphics_engine::model_loading::model::{DrawStats, Model};
+use rust_graphics_engine::camera::Camera;
+use rust_graphics_engine::orbit_controller::OrbitController;
+use rust_graphics_engine::fly_controller::FlyController;
+use rust_graphics_engine::id_buffer::IdBuffer;
+use rust_graphics_engine::bounds::Aabb;
+use rust_graphics_engine::color::srgb_to_linear;
+use rust_graphics_engine::uniform_buffer::{CameraBlock, LightBlock, UniformBuffer};
+
+use std::rc::{Rc, Weak};
+use std::time::Duration;
 
 use cgmath::{
     Matrix4, Matrix3,
     Vector3, Point3,
-    Deg, SquareMatrix, perspective, Matrix,
+    Deg, SquareMatrix, Matrix,
 };
 
-use std::time::Instant;
-
-fn main() {
-    let start_time = Instant::now();
-
-    // ---------------- WINDOW + CONTEXT ----------------
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
-        .with_title("LWA-Graphics-Engine");
-
-    let gl_context = ContextBuilder::new()
-        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-        .build_windowed(window, &event_loop)
-        .expect("Cannot create windowed context");
+struct ModelViewer {
+    assets: AssetManager,
+    model: Handle<Model>,
+    /// The model drawn: the last one whose shaders compiled. A hot reload whose shaders fail
+    /// leaves the previous one on screen.
+    shown_model: Rc<Model>,
+    /// The last model `prepare_model` was run on, so a failure is not retried every frame.
+    prepared_model: Weak<Model>,
+    model_matrix: Matrix4<f32>,
+    inverse_model_matrix: glam::Mat4,
+    model_bounds: Aabb,
+
+    lighting_shaders: ShaderVariants,
+    id_shader: Handle<Shader>,
+    camera_ubo: UniformBuffer<CameraBlock>,
+    light_ubo: UniformBuffer<LightBlock>,
+
+    camera: Camera,
+    /// Pose at the previous tick, blended with `camera` when rendering.
+    previous_camera: Camera,
+    fly: FlyController,
+    orbit: OrbitController,
+    orbit_mode: bool,
+
+    id_buffer: IdBuffer,
+    gpu_picking: bool,
+    pick_start: (u32, u32),
+    pending_pick: Option<((u32, u32), (u32, u32))>,
+
+    last_stats: DrawStats,
+}
 
-    let gl_context = unsafe { gl_context.make_current().unwrap() };
-    gl::load_with(|ptr| gl_context.get_proc_address(ptr) as *const _);
+impl ModelViewer {
 
-    unsafe {
-        gl::Enable(gl::DEPTH_TEST);
+    /// Compiles the shader variants the loaded model needs, then shows it and frames it.
+    fn prepare_model(&mut self) -> anyhow::Result<()> {
+        let model = self.model.get();
+        self.prepared_model = Rc::downgrade(&model);
+        for defines in model.material_defines() {
+            let lighting_shader = self.lighting_shaders.get(&defines).map_err(anyhow::Error::msg)?;
+            lighting_shader.bind_uniform_block::<CameraBlock>().map_err(anyhow::Error::msg)?;
+            lighting_shader.bind_uniform_block::<LightBlock>().map_err(anyhow::Error::msg)?;
+        }
+        if !model.meshes.is_empty() {
+            let model_matrix = glam::Mat4::from_cols_array_2d(&self.model_matrix.into());
+            self.model_bounds = model.bounding_box().transformed(&model_matrix);
+        }
+        self.shown_model = model;
+        Ok(())
     }
+}
 
-    // ---------------- SHADERS ----------------
-    let lighting_shader = Shader::new(
-        "resources/shaders/basic_lighting.vs",
-        "resources/shaders/basic_lighting.fs",
-    ).expect("Failed to create lighting shader");
-
-    let _light_cube_shader = Shader::new(
-        "resources/shaders/light_cube.vs",
-        "resources/shaders/light_cube.fs",
-    ).expect("Failed to create light cube shader");
-
-    // ---------------- MODEL ----------------
-    let model = Model::load("resources/assets/miku.obj")
-        .expect("Failed to load model");
-
-    // ---------------- CAMERA ----------------
-    let mut camera = Camera::new(
-        Point3::new(0.0, 0.0, 4.0),
-        -90.0,
-        0.0,
-    );
-
-    let mut w_pressed = false;
-    let mut s_pressed = false;
-    let mut a_pressed = false;
-    let mut d_pressed = false;
-
-    let mut last_frame_time = 0.0;
-
-    // ---------------- EVENT LOOP ----------------
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-
-        match event {
-            Event::LoopDestroyed => return,
-
-            // ---------------- WINDOW EVENTS ----------------
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
-                }
-
-                WindowEvent::Resized(size) => {
-                    gl_context.resize(size);
-                    unsafe {
-                        gl::Viewport(0, 0, size.width as i32, size.height as i32);
-                    }
-                }
+impl App for ModelViewer {
+
+    fn init(ctx: &mut AppContext) -> anyhow::Result<Self> {
+        // ---------------- MODEL ----------------
+        // o modelo carrega em segundo plano; até lá o handle aponta para um modelo vazio
+        let mut assets = AssetManager::new();
+        let model = assets.model_async("resources/assets/miku.obj")?;
+        // editar o .obj, o .mtl ou as texturas com o app aberto recarrega o modelo
+        assets.enable_hot_reload(Duration::from_millis(250));
+        assets.on_loaded(|event| {
+            match &event.error {
+                None => println!("loaded {}", event.path),
+                Some(error) => eprintln!("failed to load {}: {}", event.path, error),
+            }
+            for error in &event.map_errors {
+                eprintln!("{}", error);
+            }
+        });
+
+        // ---------------- SHADERS ----------------
+        // one variant of the lighting shader per material layout (#defines) used by the model
+        let lighting_shaders = ShaderVariants::new(
+            "resources/shaders/basic_lighting.vs",
+            "resources/shaders/basic_lighting.fs",
+        )
+        .with_cache(ProgramCache::new("target/shader_cache"));
+
+        let id_shader = assets.shader(
+            "resources/shaders/object_id.vs",
+            "resources/shaders/object_id.fs",
+        )?;
+
+        // ---------------- UNIFORM BUFFERS ----------------
+        let camera_ubo = UniformBuffer::<CameraBlock>::new();
+        let light_ubo = UniformBuffer::<LightBlock>::new();
+
+        id_shader.get().bind_uniform_block::<CameraBlock>().map_err(anyhow::Error::msg)?;
+
+        // ---------------- CAMERA ----------------
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 4.0),
+            -90.0,
+            0.0,
+        );
+
+        let (width, height) = ctx.size();
+        let id_buffer = IdBuffer::new(width, height).map_err(anyhow::Error::msg)?;
+
+        let model_matrix = Matrix4::from_angle_x(Deg(90.0));
+        let glam_model_matrix = glam::Mat4::from_cols_array_2d(&model_matrix.into());
+
+        Ok(ModelViewer {
+            assets,
+            shown_model: model.get(),
+            model,
+            prepared_model: Weak::new(),
+            model_matrix,
+            inverse_model_matrix: glam_model_matrix.inverse(),
+            model_bounds: Aabb::from_points([glam::Vec3::ZERO]),
+            lighting_shaders,
+            id_shader,
+            camera_ubo,
+            light_ubo,
+            previous_camera: camera.clone(),
+            camera,
+            fly: FlyController::new(),
+            orbit: OrbitController::new(Point3::new(0.0, 0.0, 0.0), 4.0),
+            orbit_mode: false,
+            id_buffer,
+            gpu_picking: false,
+            pick_start: (0, 0),
+            pending_pick: None,
+            last_stats: DrawStats::default(),
+        })
+    }
 
-                WindowEvent::Focused(true) => {
-                    let window = gl_context.window();
-                    if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
-                        let _ = window.set_cursor_grab(CursorGrabMode::Confined);
-                    }
-                    window.set_cursor_visible(false);
-                }
+    fn on_resize(&mut self, _ctx: &mut AppContext, width: u32, height: u32) {
+        self.camera.on_resize(width, height);
+        self.previous_camera.on_resize(width, height);
+        self.id_buffer.resize(width, height).expect("Failed to resize id buffer");
+    }
 
-                WindowEvent::Focused(false) => {
-                    let window = gl_context.window();
-                    let _ = window.set_cursor_grab(CursorGrabMode::None);
-                    window.set_cursor_visible(true);
-                }
+    // controles em resources/input/bindings.txt.
+    // Tab alterna entre a câmera livre (WASD) e a órbita em volta do modelo:
+    // botão esquerdo gira, botão direito move o alvo, scroll dá zoom e F enquadra o modelo.
+    // Na órbita o cursor fica visível e o botão do meio seleciona o triângulo sob ele.
+    // G troca o picking por raio pela passada de IDs na GPU, onde arrastar o botão do meio
+    // faz seleção por retângulo
+    fn update(&mut self, ctx: &mut AppContext, delta_time: f32) {
+        self.previous_camera = self.camera.clone();
+
+        if ctx.input.pressed("quit") {
+            ctx.exit();
+        }
+        if ctx.input.pressed("toggle_orbit") {
+            self.orbit_mode = !self.orbit_mode;
+            ctx.set_cursor_grab(!self.orbit_mode);
+            if self.orbit_mode {
+                let center = self.model_bounds.center();
+                self.orbit.sync_from(&self.camera, Point3::new(center.x, center.y, center.z));
+            }
+        }
+        if ctx.input.pressed("toggle_gpu_picking") {
+            self.gpu_picking = !self.gpu_picking;
+            println!("GPU picking {}", if self.gpu_picking { "on" } else { "off" });
+        }
 
-                WindowEvent::KeyboardInput { input, .. } => {
-                    if let Some(key) = input.virtual_keycode {
-                        let pressed = input.state == ElementState::Pressed;
-
-                        match key {
-                            VirtualKeyCode::W => w_pressed = pressed,
-                            VirtualKeyCode::S => s_pressed = pressed,
-                            VirtualKeyCode::A => a_pressed = pressed,
-                            VirtualKeyCode::D => d_pressed = pressed,
-                            VirtualKeyCode::Escape if pressed => {
-                                *control_flow = ControlFlow::Exit;
-                            }
-                            _ => (),
-                        }
-                    }
-                }
+        let input = &ctx.input;
+        if !self.orbit_mode {
+            self.fly.update(input, &mut self.camera, delta_time);
+            return;
+        }
 
-                _ => (),
-            },
+        if input.pressed("frame_model") {
+            self.orbit.frame(&self.model_bounds, &mut self.camera);
+        }
+        let (dx, dy) = (input.axis("orbit_x"), input.axis("orbit_y"));
+        if input.held("orbit_rotate") {
+            self.orbit.rotate(dx, dy);
+        } else if input.held("orbit_pan") {
+            self.orbit.pan(dx, dy);
+        }
+        self.orbit.zoom(input.axis("zoom"));
 
-            // ---------------- MOUSE INPUT ----------------
-            Event::DeviceEvent {
-                event: DeviceEvent::MouseMotion { delta },
-                ..
-            } => {
-                camera.process_mouse(delta.0 as f32, -delta.1 as f32);
+        let (x, y) = input.cursor_position();
+        let cursor = (x as u32, y as u32);
+        if self.gpu_picking {
+            if input.pressed("pick") {
+                self.pick_start = cursor;
+            }
+            if input.released("pick") {
+                // resolvido no render, depois da passada de IDs
+                self.pending_pick = Some((self.pick_start, cursor));
             }
+        } else if input.pressed("pick") {
+            let (width, height) = ctx.size();
+            let ray = self.camera
+                .screen_to_ray(x, y, width as f32, height as f32)
+                .transformed(&self.inverse_model_matrix);
+            match self.shown_model.pick(&ray) {
+                Some(hit) => println!(
+                    "hit mesh {} triangle {} at {:.3} (barycentric {:?})",
+                    hit.mesh_index, hit.triangle_index, hit.distance, hit.barycentric
+                ),
+                None => println!("no hit"),
+            }
+        }
 
-            // ---------------- RENDER ----------------
-            Event::RedrawRequested(_) => {
-                let current_time = start_time.elapsed().as_secs_f32();
-                let delta_time = current_time - last_frame_time;
-                last_frame_time = current_time;
+        self.orbit.update(&mut self.camera, delta_time);
+    }
 
-                if w_pressed {
-                    camera.process_keyboard(CameraMovement::Forward, delta_time);
-                }
-                if s_pressed {
-                    camera.process_keyboard(CameraMovement::Backward, delta_time);
-                }
-                if a_pressed {
-                    camera.process_keyboard(CameraMovement::Left, delta_time);
-                }
-                if d_pressed {
-                    camera.process_keyboard(CameraMovement::Right, delta_time);
-                }
+    fn render(&mut self, ctx: &mut AppContext, alpha: f32) {
+        // envia para a GPU o que os workers já decodificaram, sem travar o quadro
+        self.assets.update(Duration::from_millis(4));
+        let prepare = !self.assets.is_loading()
+            && !Weak::ptr_eq(&self.prepared_model, &Rc::downgrade(&self.model.get()));
+        if prepare && let Err(e) = self.prepare_model() {
+            eprintln!("failed to set up the loaded model, keeping the previous one: {:#}", e);
+        }
 
-                unsafe {
-                    gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-                }
+        let model_matrix = self.model_matrix;
+        let model = self.shown_model.clone();
+        let camera = self.previous_camera.interpolated(&self.camera, alpha);
 
-                // ---------------- MATRICES ----------------
-                let view = camera.get_view_matrix();
-                let projection = perspective(
-                    Deg(45.0),
-                    800.0 / 600.0,
-                    0.1,
-                    100.0,
-                );
-
-                let model_matrix =
-    Matrix4::from_angle_x(Deg(90.0));
-
-                let normal_matrix = Matrix3::from_cols(
-                    model_matrix.x.truncate(),
-                    model_matrix.y.truncate(),
-                    model_matrix.z.truncate(),
-                )
-                .invert()
-                .unwrap()
-                .transpose();
-
-                // ---------------- LIGHT ----------------
-                let light_pos = Vector3::new(1.0, 1.0, 1.0);
-                let light_color = Vector3::new(1.0, 1.0, 1.0);
-
-                let diffuse_color = light_color * 0.8;
-                let ambient_color = diffuse_color * 0.2;
-
-                let camera_pos = Vector3::new(
-                    camera.position.x,
-                    camera.position.y,
-                    camera.position.z,
-                );
-
-                // ---------------- SHADER UNIFORMS ----------------
-                lighting_shader.use_program();
-
-                lighting_shader.set_mat4("model", &model_matrix);
-                lighting_shader.set_mat4("view", &view);
-                lighting_shader.set_mat4("projection", &projection);
-                lighting_shader.set_mat3("normalMatrix", &normal_matrix);
-
-                lighting_shader.set_vec3("viewPos", &camera_pos);
-
-                lighting_shader.set_vec3("light.position", &light_pos);
-                lighting_shader.set_vec3("light.ambient", &ambient_color);
-                lighting_shader.set_vec3("light.diffuse", &diffuse_color);
-                lighting_shader.set_vec3("light.specular", &Vector3::new(1.0, 1.0, 1.0));
-
-                lighting_shader.set_vec3("material.ambient", &Vector3::new(0.2, 0.2, 0.2));
-                lighting_shader.set_vec3("material.diffuse", &Vector3::new(0.525, 0.808, 0.796));
-                lighting_shader.set_vec3("material.specular", &Vector3::new(1.0, 1.0, 1.0));
-                lighting_shader.set_float("material.shininess", 32.0);
-
-                // ---------------- DRAW ----------------
-                model.draw(lighting_shader.id);
-
-                gl_context.swap_buffers().unwrap();
-            }
+        // o framebuffer é sRGB: a cor de limpeza também é linear
+        let background = srgb_to_linear(0.1);
+        unsafe {
+            gl::ClearColor(background, background, background, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
 
-            Event::MainEventsCleared => {
-                gl_context.window().request_redraw();
+        // ---------------- MATRICES ----------------
+        let view = camera.get_view_matrix();
+        let projection = camera.get_projection_matrix();
+
+        let normal_matrix = Matrix3::from_cols(
+            model_matrix.x.truncate(),
+            model_matrix.y.truncate(),
+            model_matrix.z.truncate(),
+        )
+        .invert()
+        .unwrap()
+        .transpose();
+
+        // ---------------- LIGHT ----------------
+        let light_pos = Vector3::new(1.0, 1.0, 1.0);
+        let light_color = Vector3::new(1.0, 1.0, 1.0);
+
+        let diffuse_color = light_color * 0.8;
+        let ambient_color = diffuse_color * 0.2;
+
+        // ---------------- PER-FRAME BLOCKS ----------------
+        self.camera_ubo.update(&CameraBlock::new(&view, &projection, camera.position));
+        self.light_ubo.update(&LightBlock::new(
+            light_pos,
+            ambient_color,
+            diffuse_color,
+            Vector3::new(1.0, 1.0, 1.0),
+        ));
+
+        // ---------------- ID PASS ----------------
+        if let Some((start, end)) = self.pending_pick.take() {
+            self.id_buffer.begin();
+            let id_shader = self.id_shader.get();
+            id_shader.use_program();
+            id_shader.set("model", model_matrix);
+            model.draw_ids(&id_shader, 1);
+            self.id_buffer.end();
+
+            if start == end {
+                match self.id_buffer.read(end.0, end.1) {
+                    Some(hit) => {
+                        let (width, height) = ctx.size();
+                        let position = camera.screen_to_world(
+                            end.0 as f32,
+                            end.1 as f32,
+                            hit.depth,
+                            width as f32,
+                            height as f32,
+                        );
+                        println!("hit mesh {} at {:?}", hit.id - 1, position);
+                    }
+                    None => println!("no hit"),
+                }
+            } else {
+                let meshes: Vec<u32> = self.id_buffer
+                    .read_rect(start, end, camera.reverse_z)
+                    .iter()
+                    .map(|hit| hit.id - 1)
+                    .collect();
+                println!("selected meshes {:?}", meshes);
             }
+        }
 
-            _ => (),
+        // ---------------- DRAW ----------------
+        let frustum = camera.get_frustum(&model_matrix);
+        let lighting_shaders = &mut self.lighting_shaders;
+        let stats = model.for_each_visible(&frustum, |mesh| {
+            let lighting_shader = lighting_shaders
+                .get(&mesh.material.defines())
+                .expect("Failed to create lighting shader");
+
+            lighting_shader.use_program();
+            lighting_shader.set("model", model_matrix);
+            lighting_shader.set("normalMatrix", normal_matrix);
+
+            mesh.draw(lighting_shader);
+        });
+
+        if self.assets.is_loading() {
+            let progress = self.assets.progress();
+            ctx.window().set_title(&format!(
+                "LWA-Graphics-Engine - loading {}/{}",
+                progress.done, progress.total
+            ));
+        } else if stats != self.last_stats || prepare {
+            ctx.window().set_title(&format!(
+                "LWA-Graphics-Engine - {} meshes drawn, {} culled",
+                stats.drawn, stats.culled
+            ));
+            self.last_stats = stats;
         }
-    });
+    }
+}
+
+fn main() {
+    // com resources.pak (cargo run --bin pack) os recursos vêm do arquivo, montado antes das
+    // teclas; sem ele, de resources/
+    app::run::<ModelViewer>(AppConfig {
+        archive: Some("resources.pak".to_string()),
+        bindings: Some("resources/input/bindings.txt".to_string()),
+        ..AppConfig::default()
+    }.with_args());
 }
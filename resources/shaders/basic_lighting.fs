@@ -10,33 +10,59 @@ struct Material {
 	float shininess;
 };
 
-struct Light {
+layout (std140) uniform Camera {
+	mat4 view;
+	mat4 projection;
+	vec3 viewPos; //posição da camera
+};
+
+layout (std140) uniform Light {
 
 	vec3 position;
 	vec3 ambient;
 	vec3 diffuse;
 	vec3 specular;
-};
+} light;
 
 
 in vec3 Normal;
 in vec3 FragPos;
+in vec2 TexCoords;
 
-uniform vec3 viewPos; //posição da camera
 uniform Material material;
-uniform Light light;
+
+//Definidos por Material::defines() para cada textura do material
+#ifdef HAS_TEXTURE_DIFFUSE1
+uniform sampler2D texture_diffuse1;
+#endif
+#ifdef HAS_TEXTURE_SPECULAR1
+uniform sampler2D texture_specular1;
+#endif
 
 
 void main() {
 
+	vec3 diffuseColor = material.diffuse;
+	vec3 ambientColor = material.ambient;
+#ifdef HAS_TEXTURE_DIFFUSE1
+	vec3 texColor = texture(texture_diffuse1, TexCoords).rgb;
+	diffuseColor *= texColor;
+	ambientColor *= texColor;
+#endif
+
+	vec3 specularColor = material.specular;
+#ifdef HAS_TEXTURE_SPECULAR1
+	specularColor *= texture(texture_specular1, TexCoords).rgb;
+#endif
+
 	//ambient light
-	vec3 ambient = light.ambient * material.ambient;
+	vec3 ambient = light.ambient * ambientColor;
 
 	//diffuse light
 	vec3 norm = normalize(Normal);
 	vec3 lightDir = normalize(light.position - FragPos);
 	float diff = max(dot(norm, lightDir), 0.0); //max garante que não tenhamos valores negativos
-	vec3 diffuse = light.diffuse * diff * material.diffuse; 
+	vec3 diffuse = light.diffuse * diff * diffuseColor;
 
 	//Specular light
 	vec3 viewDir = normalize(viewPos - FragPos);
@@ -45,7 +71,7 @@ void main() {
 	vec3 reflectDir = I - 2.0 * dot(norm, I) * norm;
 
 	float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess); 
-	vec3 specular = light.specular * spec * material.specular;
+	vec3 specular = light.specular * spec * specularColor;
 
 	vec3 result = ambient + diffuse + specular;
 	FragColor = vec4(result, 1.0);
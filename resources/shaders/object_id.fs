@@ -0,0 +1,10 @@
+#version 330 core
+
+// escrito num alvo R32UI; 0 fica reservado para o fundo
+out uint FragId;
+
+uniform uint objectId;
+
+void main() {
+	FragId = objectId;
+}
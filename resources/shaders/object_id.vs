@@ -0,0 +1,15 @@
+#version 330 core
+
+layout (location = 0) in vec3 aPos;
+
+layout (std140) uniform Camera {
+	mat4 view;
+	mat4 projection;
+	vec3 viewPos;
+};
+
+uniform mat4 model;
+
+void main() {
+	gl_Position = projection * view * model * vec4(aPos, 1.0);
+}
@@ -0,0 +1,315 @@
+use crate::gl_object;
+use crate::input::{Input, InputMap};
+use crate::timestep::FixedTimestep;
+use crate::vfs;
+
+use glutin::dpi::LogicalSize;
+use glutin::event::{Event, WindowEvent};
+use glutin::event_loop::{ControlFlow, EventLoop};
+use glutin::window::{CursorGrabMode, Window, WindowBuilder};
+use glutin::{Api, ContextBuilder, ContextWrapper, GlRequest, PossiblyCurrent};
+
+use std::time::Instant;
+
+/// Window and context settings for `run`.
+pub struct AppConfig {
+    pub title: String,
+    /// Initial inner size in logical pixels.
+    pub width: u32,
+    pub height: u32,
+    /// OpenGL (major, minor) core version to request.
+    pub gl_version: (u8, u8),
+    pub vsync: bool,
+    /// Ask for an sRGB-capable default framebuffer and enable `FRAMEBUFFER_SRGB`, so shaders
+    /// write linear colour and the hardware encodes it for display.
+    pub srgb: bool,
+    /// Hide and lock the cursor while the window has focus, for mouse look.
+    pub grab_cursor: bool,
+    /// Resource archive mounted before anything is loaded, bindings included (see
+    /// `vfs::mount_if_present`); runs without one use the loose files.
+    pub archive: Option<String>,
+    /// Bindings file loaded into `AppContext::input`; `None` starts with no bindings.
+    pub bindings: Option<String>,
+    /// Simulation ticks per second; `App::update` always receives `1 / tick_rate`.
+    pub tick_rate: f32,
+    /// Deterministic mode: render exactly this many frames, each advancing the simulation by
+    /// one tick whatever the real time, then exit. For tests and frame-by-frame recording.
+    pub step_frames: Option<u32>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            title: "LWA-Graphics-Engine".to_string(),
+            width: 800,
+            height: 600,
+            gl_version: (3, 3),
+            vsync: true,
+            srgb: true,
+            grab_cursor: true,
+            archive: None,
+            bindings: None,
+            tick_rate: 60.0,
+            step_frames: None,
+        }
+    }
+}
+
+impl AppConfig {
+    /// Overrides settings from the command line: `--tick-rate <hz>` and `--frames <n>`
+    /// (see `step_frames`). Unknown arguments are left alone.
+    pub fn with_args(mut self) -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        for pair in args.windows(2) {
+            match pair[0].as_str() {
+                "--tick-rate" => match pair[1].parse::<f32>() {
+                    // the same values FixedTimestep::new accepts
+                    Ok(rate) if rate.is_finite() && rate > 0.0 => self.tick_rate = rate,
+                    _ => eprintln!("Ignoring invalid --tick-rate {}", pair[1]),
+                },
+                "--frames" => match pair[1].parse() {
+                    Ok(frames) => self.step_frames = Some(frames),
+                    Err(_) => eprintln!("Ignoring invalid --frames {}", pair[1]),
+                },
+                _ => (),
+            }
+        }
+        self
+    }
+}
+
+/// What the runner owns and shares with the `App`: the window and its GL context, the input
+/// state and the clocks.
+pub struct AppContext {
+    pub input: Input,
+    gl_context: ContextWrapper<PossiblyCurrent, Window>,
+    timestep: FixedTimestep,
+    frames: u64,
+    start_time: Instant,
+    cursor_grabbed: bool,
+    exit_requested: bool,
+}
+
+impl AppContext {
+
+    pub fn window(&self) -> &Window {
+        self.gl_context.window()
+    }
+
+    /// Framebuffer size in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        let size = self.window().inner_size();
+        (size.width, size.height)
+    }
+
+    /// Real seconds since the runner started. Not reproducible: simulation code should use
+    /// `time` instead.
+    pub fn elapsed(&self) -> f32 {
+        self.start_time.elapsed().as_secs_f32()
+    }
+
+    /// Simulated seconds: the number of ticks run so far times the tick length. Inside
+    /// `App::update` that is the start of the tick being run.
+    pub fn time(&self) -> f32 {
+        self.timestep.time()
+    }
+
+    pub fn timestep(&self) -> &FixedTimestep {
+        &self.timestep
+    }
+
+    /// Frames rendered so far.
+    pub fn frame(&self) -> u64 {
+        self.frames
+    }
+
+    /// Ends the event loop after the current frame.
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Hides and locks the cursor (`true`) or frees it. Remembered across focus changes.
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        self.cursor_grabbed = grab;
+        self.apply_cursor_grab(grab);
+    }
+
+    fn apply_cursor_grab(&self, grab: bool) {
+        let window = self.window();
+        if grab {
+            if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
+                let _ = window.set_cursor_grab(CursorGrabMode::Confined);
+            }
+        } else {
+            let _ = window.set_cursor_grab(CursorGrabMode::None);
+        }
+        window.set_cursor_visible(!grab);
+    }
+}
+
+/// A program driven by `run`. Every method is called with the GL context current.
+///
+/// Each frame the runner calls `update` zero or more times, once per fixed simulation tick
+/// due, then `render` once and swaps buffers. Input presses and releases are seen by the first
+/// `update` after they happen; held buttons by every one.
+pub trait App: Sized {
+    /// Loads resources and builds the app once the window and context exist.
+    fn init(ctx: &mut AppContext) -> anyhow::Result<Self>;
+
+    /// Advances the simulation by one tick of `delta_time` seconds, always the same length.
+    fn update(&mut self, ctx: &mut AppContext, delta_time: f32);
+
+    /// Draws a frame. `alpha` (`0..1`) is how far real time has moved past the last tick
+    /// towards the next; blend the previous and current simulation state by it.
+    fn render(&mut self, ctx: &mut AppContext, alpha: f32);
+
+    /// Window events the runner does not consume itself (everything except resize and close).
+    /// Input events are also recorded in `ctx.input` before this is called.
+    fn on_event(&mut self, _ctx: &mut AppContext, _event: &WindowEvent) {}
+
+    /// Called after the context and viewport have been resized, and once right after `init`.
+    fn on_resize(&mut self, _ctx: &mut AppContext, _width: u32, _height: u32) {}
+}
+
+/// Opens the window, creates the context and runs `A` until it exits or the window is closed.
+/// Panics if the window, context, archive, bindings or `A::init` fail.
+pub fn run<A: App + 'static>(config: AppConfig) -> ! {
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(LogicalSize::new(config.width, config.height));
+
+    let gl_context = ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGl, config.gl_version))
+        .with_vsync(config.vsync)
+        .with_srgb(config.srgb)
+        .build_windowed(window, &event_loop)
+        .expect("Cannot create windowed context");
+
+    let gl_context = unsafe {
+        gl_context
+            .make_current()
+            .expect("Failed to make context current")
+    };
+
+    gl::load_with(|ptr| gl_context.get_proc_address(ptr) as *const _);
+    gl_object::begin_context();
+
+    unsafe {
+        gl::Enable(gl::DEPTH_TEST);
+        // filter across cube map face edges instead of clamping at each face
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+    }
+    if config.srgb {
+        if !gl_context.get_pixel_format().srgb {
+            eprintln!("Default framebuffer is not sRGB-capable; output will look too dark");
+        }
+        unsafe {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+        }
+    }
+
+    if let Some(archive) = &config.archive {
+        vfs::mount_if_present(archive).expect("Failed to mount the resource archive");
+    }
+    let bindings = match &config.bindings {
+        Some(path) => InputMap::load(path).expect("Failed to load input bindings"),
+        None => InputMap::new(),
+    };
+
+    let mut ctx = AppContext {
+        input: Input::new(bindings),
+        gl_context,
+        timestep: FixedTimestep::new(config.tick_rate),
+        frames: 0,
+        start_time: Instant::now(),
+        cursor_grabbed: config.grab_cursor,
+        exit_requested: false,
+    };
+
+    let mut app = A::init(&mut ctx).unwrap_or_else(|e| panic!("Failed to initialise app: {:#}", e));
+    let (width, height) = ctx.size();
+    app.on_resize(&mut ctx, width, height);
+
+    // dropped on LoopDestroyed, while the context is still alive to delete GL objects
+    let mut app = Some(app);
+    let step_frames = config.step_frames;
+    let mut last_frame_time = ctx.elapsed();
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        ctx.input.handle_event(&event);
+
+        if let Event::LoopDestroyed = event {
+            app = None;
+            // anything still alive past this point is left to the driver
+            gl_object::end_context();
+        }
+        let Some(current) = app.as_mut() else {
+            return;
+        };
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+
+                WindowEvent::Resized(size) => {
+                    ctx.gl_context.resize(size);
+                    unsafe {
+                        gl::Viewport(0, 0, size.width as i32, size.height as i32);
+                    }
+                    current.on_resize(&mut ctx, size.width, size.height);
+                }
+
+                WindowEvent::Focused(focused) => {
+                    if ctx.cursor_grabbed {
+                        ctx.apply_cursor_grab(focused);
+                    }
+                    current.on_event(&mut ctx, &event);
+                }
+
+                event => current.on_event(&mut ctx, &event),
+            },
+
+            Event::RedrawRequested(_) => {
+                let current_time = ctx.elapsed();
+                let frame_time = current_time - last_frame_time;
+                last_frame_time = current_time;
+
+                let ticks = match step_frames {
+                    Some(_) => ctx.timestep.step(1),
+                    None => ctx.timestep.advance(frame_time),
+                };
+                let delta_time = ctx.timestep.dt();
+                for _ in 0..ticks {
+                    current.update(&mut ctx, delta_time);
+                    ctx.timestep.tick();
+                    // presses are consumed by the first tick that sees them
+                    ctx.input.end_frame();
+                }
+
+                let alpha = ctx.timestep.alpha();
+                current.render(&mut ctx, alpha);
+                ctx.gl_context.swap_buffers().unwrap();
+                ctx.frames += 1;
+
+                let finished = step_frames.is_some_and(|frames| ctx.frames >= frames as u64);
+                if ctx.exit_requested || finished {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+
+            Event::MainEventsCleared => {
+                ctx.window().request_redraw();
+            }
+
+            _ => (),
+        }
+    })
+}
@@ -0,0 +1,684 @@
+use crate::material::Material;
+use crate::model_loading::mesh::Mesh;
+use crate::model_loading::model::{MeshData, Model, ModelData};
+use crate::model_loading::texture::{ColorSpace, Texture, TextureDescriptor, TextureSource};
+use crate::shader::Shader;
+use crate::vfs;
+use crate::worker_pool::WorkerPool;
+
+use anyhow::Context;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Shared reference to an asset owned by an `AssetManager`. Cloning is cheap and keeps the
+/// asset alive; once only the manager holds it, `AssetManager::release_unused` frees it.
+///
+/// The asset behind a handle can be swapped by the manager (a placeholder replaced by the
+/// loaded asset), so fetch it with `get` when using it rather than keeping the `Rc`.
+pub struct Handle<T> {
+    id: u64,
+    slot: Rc<RefCell<Rc<T>>>,
+}
+
+impl<T> Handle<T> {
+    /// Identifies the asset for the lifetime of the manager; equal handles share an id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The current asset. Holding the returned `Rc` counts as a use like any handle.
+    pub fn get(&self) -> Rc<T> {
+        self.slot.borrow().clone()
+    }
+
+    fn set(&self, asset: T) {
+        *self.slot.borrow_mut() = Rc::new(asset);
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self { id: self.id, slot: self.slot.clone() }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id)
+    }
+}
+
+/// Assets of one type by key.
+struct Cache<K, T> {
+    entries: HashMap<K, Handle<T>>,
+}
+
+impl<K: Eq + Hash, T> Cache<K, T> {
+    fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    fn get(&self, key: &K) -> Option<Handle<T>> {
+        self.entries.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: K, id: u64, asset: T) -> Handle<T> {
+        let handle = Handle { id, slot: Rc::new(RefCell::new(Rc::new(asset))) };
+        self.entries.insert(key, handle.clone());
+        handle
+    }
+
+    /// Drops every asset nobody else holds, through a handle or an `Rc` taken from one.
+    /// Returns how many went.
+    fn release_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, handle| {
+            Rc::strong_count(&handle.slot) > 1 || Rc::strong_count(&handle.slot.borrow()) > 1
+        });
+        before - self.entries.len()
+    }
+}
+
+/// How far the background loads queued since the manager was last idle have got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    /// In `0..1`; 1 when nothing is loading.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 { 1.0 } else { self.done as f32 / self.total as f32 }
+    }
+}
+
+/// A background load that finished, successfully or not. On failure the placeholder stays.
+#[derive(Clone, Debug)]
+pub struct LoadEvent {
+    pub path: String,
+    pub error: Option<String>,
+    /// Material maps of a loaded model that failed, one message each (see `Model::map_errors`).
+    pub map_errors: Vec<String>,
+}
+
+type TextureKey = (PathBuf, ColorSpace);
+/// A material map read on a worker: (path, descriptor, decoded file).
+type DecodedMap = (String, TextureDescriptor, anyhow::Result<TextureSource>);
+type LoadedCallback = Box<dyn FnMut(&LoadEvent)>;
+
+/// What a worker hands back: everything read and decoded, nothing uploaded.
+enum Decoded {
+    Texture(anyhow::Result<TextureSource>),
+    Model(anyhow::Result<(ModelData, Vec<DecodedMap>)>),
+    /// The job panicked; reported as a failed load instead of leaving the request pending.
+    Panicked,
+}
+
+/// A finished decode waiting for its GPU upload on the main thread.
+enum Upload {
+    Texture {
+        path: String,
+        descriptor: TextureDescriptor,
+        texture: Rc<Texture>,
+        source: TextureSource,
+    },
+    Model(Box<ModelUpload>),
+}
+
+/// A model uploaded one piece per step: its maps, then its materials, then each mesh.
+struct ModelUpload {
+    path: String,
+    handle: Handle<Model>,
+    directory: String,
+    maps: VecDeque<DecodedMap>,
+    /// Each map uploaded so far, or why it could not be read or decoded. Holding the handles
+    /// keeps `release_unused` from freeing the textures before the materials take them.
+    textures: HashMap<(String, ColorSpace), Result<Handle<Texture>, String>>,
+    data: Option<ModelData>,
+    materials: Vec<Rc<Material>>,
+    map_errors: Vec<String>,
+    pending_meshes: VecDeque<MeshData>,
+    meshes: Vec<Mesh>,
+}
+
+/// An asset read from a watched file.
+#[derive(Clone, PartialEq)]
+enum Watched {
+    Model(PathBuf),
+    Texture(TextureKey, TextureDescriptor),
+}
+
+/// A source file of loaded assets, polled for changes while hot reload is on.
+struct WatchedFile {
+    /// Modification time when its assets were last (re)loaded.
+    loaded: Option<SystemTime>,
+    /// Modification time at the previous poll. A change is only acted on once it has stayed
+    /// put for a whole poll, so a file still being written is not read half-way.
+    seen: Option<SystemTime>,
+    assets: Vec<Watched>,
+}
+
+/// Loads each model, texture and shader once and hands out `Handle`s to it.
+///
+/// Files are keyed by canonical path, so `./a/../a/tex.png` and `a/tex.png` share an entry.
+/// Textures are also keyed by colour space, since the same file can be needed as sRGB colour
+/// and as linear data; other descriptor settings come from the first request. Textures used by
+/// models loaded here go through the same cache.
+///
+/// Assets stay loaded while any handle (or an `Rc` taken from one) is alive. Call
+/// `release_unused` at a convenient point, e.g. after a level change, to drop the rest and
+/// their GPU objects.
+///
+/// `model_async` and `texture_async` return at once with a placeholder (an empty model, a
+/// white texture) and read and decode the file on worker threads. `update`, called once per
+/// frame, uploads the results within a time budget and swaps them in.
+///
+/// With `enable_hot_reload`, `update` also notices when the OBJ, MTL or image files of loaded
+/// models and textures change on disk and reloads them in the background, swapping them in
+/// behind the same handles (textures in place, so materials pick them up too). A reload that
+/// fails is reported like any background load and leaves the previous version in use.
+pub struct AssetManager {
+    next_id: u64,
+    models: Cache<PathBuf, Model>,
+    textures: Cache<TextureKey, Texture>,
+    shaders: Cache<(PathBuf, PathBuf), Shader>,
+    materials: Cache<String, Material>,
+
+    workers: Option<WorkerPool<(u64, Decoded)>>,
+    /// Requests sent to the workers, by request id.
+    in_flight: HashMap<u64, InFlight>,
+    uploads: VecDeque<Upload>,
+    progress: LoadProgress,
+    on_progress: Option<Box<dyn FnMut(LoadProgress)>>,
+    on_loaded: Option<LoadedCallback>,
+
+    /// Source files of the loaded assets, by canonical path.
+    watched: HashMap<PathBuf, WatchedFile>,
+    poll_interval: Option<Duration>,
+    last_poll: Instant,
+}
+
+enum InFlight {
+    Texture { path: String, descriptor: TextureDescriptor, texture: Rc<Texture> },
+    Model { path: String, handle: Handle<Model> },
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        Self {
+            next_id: 1,
+            models: Cache::new(),
+            textures: Cache::new(),
+            shaders: Cache::new(),
+            materials: Cache::new(),
+            workers: None,
+            in_flight: HashMap::new(),
+            uploads: VecDeque::new(),
+            progress: LoadProgress::default(),
+            on_progress: None,
+            on_loaded: None,
+            watched: HashMap::new(),
+            poll_interval: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn model(&mut self, path: &str) -> anyhow::Result<Handle<Model>> {
+        let key = canonical(path)?;
+        if let Some(handle) = self.models.get(&key) {
+            return Ok(handle);
+        }
+        let data = ModelData::parse(path)?;
+        let sources = data.sources.clone();
+        let model = Model::from_data(data, &mut |texture_path, descriptor| {
+            self.texture_with(texture_path, descriptor).map(|handle| handle.get())
+        });
+        let id = self.next_id();
+        let handle = self.models.insert(key.clone(), id, model);
+        self.watch_model(&key, &sources);
+        Ok(handle)
+    }
+
+    pub fn texture(&mut self, path: &str) -> anyhow::Result<Handle<Texture>> {
+        self.texture_with(path, &TextureDescriptor::default())
+    }
+
+    pub fn texture_with(&mut self, path: &str, descriptor: &TextureDescriptor) -> anyhow::Result<Handle<Texture>> {
+        let key = (canonical(path)?, descriptor.color_space);
+        if let Some(handle) = self.textures.get(&key) {
+            return Ok(handle);
+        }
+        let texture = Texture::from_file_with(path, descriptor)?;
+        let id = self.next_id();
+        self.watch(key.0.clone(), Watched::Texture(key.clone(), *descriptor));
+        Ok(self.textures.insert(key, id, texture))
+    }
+
+    /// Starts loading `path` in the background and returns at once with a handle to an empty
+    /// model, which becomes the loaded one once `update` has uploaded it. Only a missing file
+    /// fails here; later failures go to the `on_loaded` callback and leave the model empty.
+    pub fn model_async(&mut self, path: &str) -> anyhow::Result<Handle<Model>> {
+        let key = canonical(path)?;
+        if let Some(handle) = self.models.get(&key) {
+            return Ok(handle);
+        }
+        let id = self.next_id();
+        let handle = self.models.insert(key.clone(), id, Model::empty());
+        // watched from the start, so fixing a file that failed to load reloads it; the MTL
+        // files are only known once it has been parsed
+        self.watch(key.clone(), Watched::Model(key));
+        self.spawn_model(path, handle.clone());
+        Ok(handle)
+    }
+
+    fn spawn_model(&mut self, path: &str, handle: Handle<Model>) {
+        let job_path = path.to_string();
+        self.spawn(InFlight::Model { path: path.to_string(), handle }, move || {
+            Decoded::Model(ModelData::parse(&job_path).map(|data| {
+                let maps = data
+                    .texture_maps()
+                    .into_iter()
+                    .map(|(path, descriptor)| {
+                        let source = TextureSource::from_file(&path);
+                        (path, descriptor, source)
+                    })
+                    .collect();
+                (data, maps)
+            }))
+        });
+    }
+
+    /// Starts loading `path` in the background and returns at once with a handle to a 1x1
+    /// white texture, whose contents are replaced in place once `update` has uploaded the
+    /// file, so materials already using it pick it up.
+    pub fn texture_async(&mut self, path: &str, descriptor: &TextureDescriptor) -> anyhow::Result<Handle<Texture>> {
+        let key = (canonical(path)?, descriptor.color_space);
+        if let Some(handle) = self.textures.get(&key) {
+            return Ok(handle);
+        }
+        let mut placeholder = Texture::placeholder();
+        placeholder.path = path.to_string();
+        let id = self.next_id();
+        self.watch(key.0.clone(), Watched::Texture(key.clone(), *descriptor));
+        let handle = self.textures.insert(key, id, placeholder);
+        self.spawn_texture(path, descriptor, handle.get());
+        Ok(handle)
+    }
+
+    fn spawn_texture(&mut self, path: &str, descriptor: &TextureDescriptor, texture: Rc<Texture>) {
+        let job_path = path.to_string();
+        self.spawn(
+            InFlight::Texture { path: path.to_string(), descriptor: *descriptor, texture },
+            move || Decoded::Texture(TextureSource::from_file(&job_path)),
+        );
+    }
+
+    fn spawn(&mut self, request: InFlight, job: impl FnOnce() -> Decoded + Send + 'static) {
+        let request_id = self.next_id();
+        self.in_flight.insert(request_id, request);
+        self.progress.total += 1;
+        self.workers
+            .get_or_insert_with(WorkerPool::with_default_threads)
+            .spawn(move || {
+                let decoded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                (request_id, decoded.unwrap_or(Decoded::Panicked))
+            });
+    }
+
+    /// Called with the progress every time a background load finishes.
+    pub fn on_progress(&mut self, callback: impl FnMut(LoadProgress) + 'static) {
+        self.on_progress = Some(Box::new(callback));
+    }
+
+    /// Called for every background load that finishes, including failed ones.
+    pub fn on_loaded(&mut self, callback: impl FnMut(&LoadEvent) + 'static) {
+        self.on_loaded = Some(Box::new(callback));
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    pub fn is_loading(&self) -> bool {
+        !self.in_flight.is_empty() || !self.uploads.is_empty()
+    }
+
+    /// Collects decoded files from the workers and uploads them, one texture or mesh at a
+    /// time, until `budget` is spent (at least one step runs if anything is waiting). Call it
+    /// once per frame from the thread that owns the GL context.
+    pub fn update(&mut self, budget: Duration) {
+        let start = Instant::now();
+        self.poll_watched();
+        while let Some((request_id, decoded)) = self.workers.as_ref().and_then(|w| w.try_recv()) {
+            if let Some(request) = self.in_flight.remove(&request_id) {
+                self.receive(request, decoded);
+            }
+        }
+
+        while !self.uploads.is_empty() {
+            self.upload_step();
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    fn receive(&mut self, request: InFlight, decoded: Decoded) {
+        match (request, decoded) {
+            (InFlight::Texture { path, descriptor, texture }, Decoded::Texture(source)) => match source {
+                Ok(source) => self.uploads.push_back(Upload::Texture { path, descriptor, texture, source }),
+                Err(e) => self.finish(path, Some(format!("{:#}", e))),
+            },
+            (InFlight::Model { path, handle }, Decoded::Model(result)) => match result {
+                Ok((mut data, maps)) => {
+                    if let Ok(key) = canonical(&path) {
+                        self.watch_model(&key, &data.sources);
+                    }
+                    let pending_meshes = std::mem::take(&mut data.meshes).into();
+                    self.uploads.push_back(Upload::Model(Box::new(ModelUpload {
+                        path,
+                        handle,
+                        directory: data.directory.clone(),
+                        maps: maps.into(),
+                        textures: HashMap::new(),
+                        data: Some(data),
+                        materials: Vec::new(),
+                        map_errors: Vec::new(),
+                        pending_meshes,
+                        meshes: Vec::new(),
+                    })));
+                }
+                Err(e) => self.finish(path, Some(format!("{:#}", e))),
+            },
+            (InFlight::Texture { path, .. } | InFlight::Model { path, .. }, Decoded::Panicked) => {
+                self.finish(path.clone(), Some(format!("Loading {} panicked", path)))
+            }
+            _ => unreachable!("worker result does not match its request"),
+        }
+    }
+
+    /// Runs one upload step of the oldest pending upload.
+    fn upload_step(&mut self) {
+        let Some(upload) = self.uploads.pop_front() else {
+            return;
+        };
+        match upload {
+            Upload::Texture { path, descriptor, texture, source } => {
+                match Texture::from_source(&source, &descriptor) {
+                    Ok(loaded) => {
+                        texture.replace(loaded);
+                        self.finish(path, None);
+                    }
+                    Err(e) => self.finish(path.clone(), Some(format!("Failed to upload texture {}: {:#}", path, e))),
+                }
+            }
+            Upload::Model(mut model) => {
+                if self.model_step(&mut model) {
+                    let ModelUpload { path, handle, directory, meshes, map_errors, .. } = *model;
+                    handle.set(Model { meshes, directory, map_errors: map_errors.clone() });
+                    self.report(LoadEvent { path, error: None, map_errors });
+                } else {
+                    self.uploads.push_front(Upload::Model(model));
+                }
+            }
+        }
+    }
+
+    /// One texture, the materials, or one mesh. True once the model is complete.
+    fn model_step(&mut self, model: &mut ModelUpload) -> bool {
+        if let Some((path, descriptor, source)) = model.maps.pop_front() {
+            let key = (path.clone(), descriptor.color_space);
+            let result = source.and_then(|source| {
+                let key = (canonical(&path)?, descriptor.color_space);
+                if let Some(handle) = self.textures.get(&key) {
+                    return Ok(handle);
+                }
+                let mut texture = Texture::from_source(&source, &descriptor)
+                    .with_context(|| format!("Failed to upload texture {}", path))?;
+                texture.path = path.clone();
+                let id = self.next_id();
+                self.watch(key.0.clone(), Watched::Texture(key.clone(), descriptor));
+                Ok(self.textures.insert(key, id, texture))
+            });
+            model.textures.insert(key, result.map_err(|e| format!("{:#}", e)));
+            return false;
+        }
+
+        if let Some(data) = model.data.take() {
+            let textures = &model.textures;
+            (model.materials, model.map_errors) = Model::build_materials(&data, &mut |path, descriptor| {
+                match textures.get(&(path.to_string(), descriptor.color_space)) {
+                    Some(Ok(handle)) => Ok(handle.get()),
+                    Some(Err(error)) => anyhow::bail!("{}", error),
+                    None => anyhow::bail!("texture {} was not loaded with the model", path),
+                }
+            });
+            return false;
+        }
+
+        if let Some(mesh) = model.pending_meshes.pop_front() {
+            let default_material = Rc::new(Material::default());
+            model.meshes.push(Model::build_mesh(mesh, &model.materials, &default_material));
+        }
+        model.pending_meshes.is_empty()
+    }
+
+    fn finish(&mut self, path: String, error: Option<String>) {
+        self.report(LoadEvent { path, error, map_errors: Vec::new() });
+    }
+
+    /// Counts a finished load and hands it to the callbacks, the only place failures go.
+    fn report(&mut self, event: LoadEvent) {
+        self.progress.done += 1;
+        let progress = self.progress;
+        if !self.is_loading() {
+            self.progress = LoadProgress::default();
+        }
+        if let Some(callback) = &mut self.on_loaded {
+            callback(&event);
+        }
+        if let Some(callback) = &mut self.on_progress {
+            callback(progress);
+        }
+    }
+
+    pub fn shader(&mut self, vertex_path: &str, fragment_path: &str) -> anyhow::Result<Handle<Shader>> {
+        let key = (canonical(vertex_path)?, canonical(fragment_path)?);
+        if let Some(handle) = self.shaders.get(&key) {
+            return Ok(handle);
+        }
+        let shader = Shader::new(vertex_path, fragment_path).map_err(anyhow::Error::msg)?;
+        let id = self.next_id();
+        Ok(self.shaders.insert(key, id, shader))
+    }
+
+    /// Registers `material` under `name`, replacing any material registered with that name
+    /// (handles to the old one keep it alive).
+    pub fn add_material(&mut self, name: &str, material: Material) -> Handle<Material> {
+        let id = self.next_id();
+        self.materials.insert(name.to_string(), id, material)
+    }
+
+    pub fn material(&self, name: &str) -> Option<Handle<Material>> {
+        self.materials.get(&name.to_string())
+    }
+
+    /// Drops every asset no handle refers to anymore, deleting its GPU objects, and returns
+    /// how many were released. Models go first so the textures only they used follow in the
+    /// same call. Assets still loading are kept.
+    pub fn release_unused(&mut self) -> usize {
+        let released = self.models.release_unused()
+            + self.materials.release_unused()
+            + self.textures.release_unused()
+            + self.shaders.release_unused();
+
+        let (models, textures) = (&self.models, &self.textures);
+        self.watched.retain(|_, file| {
+            file.assets.retain(|asset| match asset {
+                Watched::Model(key) => models.get(key).is_some(),
+                Watched::Texture(key, _) => textures.get(key).is_some(),
+            });
+            !file.assets.is_empty()
+        });
+        released
+    }
+
+    /// Starts checking the source files of loaded models and textures every `poll_interval`
+    /// (from `update`) and reloading what changed. A few hundred milliseconds is plenty.
+    pub fn enable_hot_reload(&mut self, poll_interval: Duration) {
+        self.poll_interval = Some(poll_interval);
+    }
+
+    pub fn disable_hot_reload(&mut self) {
+        self.poll_interval = None;
+    }
+
+    fn watch(&mut self, file: PathBuf, asset: Watched) {
+        let file = self.watched.entry(file).or_insert_with_key(|file| {
+            let modified = modified_time(file);
+            WatchedFile { loaded: modified, seen: modified, assets: Vec::new() }
+        });
+        if !file.assets.contains(&asset) {
+            file.assets.push(asset);
+        }
+    }
+
+    /// Watches the OBJ and MTL files a model was read from.
+    fn watch_model(&mut self, key: &Path, sources: &[String]) {
+        for source in sources {
+            // an MTL file that does not exist yet is watched under its plain path
+            let file = canonical(source).unwrap_or_else(|_| PathBuf::from(source));
+            self.watch(file, Watched::Model(key.to_path_buf()));
+        }
+    }
+
+    /// Queues a reload of the assets of every watched file whose modification time changed
+    /// and then stayed the same for one poll.
+    fn poll_watched(&mut self) {
+        let Some(poll_interval) = self.poll_interval else {
+            return;
+        };
+        if self.last_poll.elapsed() < poll_interval {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, file) in &mut self.watched {
+            let modified = modified_time(path);
+            if modified != file.seen {
+                file.seen = modified;
+            } else if modified.is_some() && modified != file.loaded {
+                // a file that vanished (editors often save by replacing it) waits to reappear
+                changed.push(path.clone());
+            }
+        }
+
+        for path in changed {
+            let file = &self.watched[&path];
+            // reloading an asset that is still loading could finish out of order; retry at
+            // the next poll instead
+            if file.assets.iter().any(|asset| self.is_pending(asset)) {
+                continue;
+            }
+            let assets = file.assets.clone();
+            if let Some(file) = self.watched.get_mut(&path) {
+                file.loaded = file.seen;
+            }
+            for asset in assets {
+                self.reload(asset);
+            }
+        }
+    }
+
+    fn reload(&mut self, asset: Watched) {
+        match asset {
+            Watched::Model(key) => {
+                if let Some(handle) = self.models.get(&key) {
+                    self.spawn_model(&key.to_string_lossy(), handle);
+                }
+            }
+            Watched::Texture(key, descriptor) => {
+                if let Some(handle) = self.textures.get(&key) {
+                    let texture = handle.get();
+                    let path = texture.path.clone();
+                    self.spawn_texture(&path, &descriptor, texture);
+                }
+            }
+        }
+    }
+
+    /// Whether `asset` is waiting for a worker or for its upload.
+    fn is_pending(&self, asset: &Watched) -> bool {
+        match asset {
+            Watched::Model(key) => self.models.get(key).is_some_and(|handle| {
+                self.in_flight.values().any(|r| matches!(r, InFlight::Model { handle: h, .. } if *h == handle))
+                    || self.uploads.iter().any(|u| matches!(u, Upload::Model(m) if m.handle == handle))
+            }),
+            Watched::Texture(key, _) => self.textures.get(key).is_some_and(|handle| {
+                let texture = handle.get();
+                self.in_flight
+                    .values()
+                    .any(|r| matches!(r, InFlight::Texture { texture: t, .. } if Rc::ptr_eq(t, &texture)))
+                    || self
+                        .uploads
+                        .iter()
+                        .any(|u| matches!(u, Upload::Texture { texture: t, .. } if Rc::ptr_eq(t, &texture)))
+            }),
+        }
+    }
+
+    /// Number of (models, textures, shaders, materials) currently loaded.
+    pub fn counts(&self) -> (usize, usize, usize, usize) {
+        (
+            self.models.entries.len(),
+            self.textures.entries.len(),
+            self.shaders.entries.len(),
+            self.materials.entries.len(),
+        )
+    }
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn canonical(path: &str) -> anyhow::Result<PathBuf> {
+    match std::fs::canonicalize(path) {
+        Ok(path) => Ok(path),
+        // only in a mounted archive, whose paths have a single form already
+        Err(_) if vfs::in_archive(path) => Ok(PathBuf::from(vfs::normalize(path))),
+        Err(e) => Err(e).with_context(|| format!("Failed to resolve asset path {}", path)),
+    }
+}
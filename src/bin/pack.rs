@@ -0,0 +1,86 @@
+//! Bundles the models, textures, shaders and text files under one or more directories into a single
+//! archive, read through `vfs` once mounted.
+//!
+//! Usage: `cargo run --bin pack -- [-o OUT] [DIR...]` (defaults to `resources` into
+//! `resources.pak`).
+//!
+//! Files are stored under their path as reached from the command line arguments
+//! (`resources/shaders/basic_lighting.vs`), which is the path the code loads them by: run it
+//! from the directory the demos are run from. Files of other types are left out.
+
+use rust_graphics_engine::vfs::{self, Archive};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const EXTENSIONS: &[&str] = &[
+    // models
+    "obj", "mtl",
+    // textures
+    "png", "jpg", "jpeg", "tga", "bmp", "hdr", "ktx2", "dds",
+    // shaders
+    "vs", "fs", "vert", "frag", "comp", "glsl",
+    // bindings, camera paths
+    "txt",
+];
+
+fn main() -> ExitCode {
+    let mut out = PathBuf::from("resources.pak");
+    let mut dirs = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            let Some(path) = args.next() else {
+                eprintln!("error: -o needs a path");
+                return ExitCode::from(2);
+            };
+            out = PathBuf::from(path);
+        } else {
+            dirs.push(PathBuf::from(arg));
+        }
+    }
+    if dirs.is_empty() {
+        dirs.push(PathBuf::from("resources"));
+    }
+
+    let mut files = Vec::new();
+    for dir in &dirs {
+        if let Err(e) = collect_files(dir, &mut files) {
+            eprintln!("error: cannot read {}: {}", dir.display(), e);
+            return ExitCode::from(2);
+        }
+    }
+    files.sort();
+
+    let entries: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|path| (vfs::normalize(&path.to_string_lossy()), path))
+        .collect();
+    for (name, _) in &entries {
+        println!("add  {}", name);
+    }
+
+    if let Err(e) = Archive::create(&out, &entries) {
+        eprintln!("error: {:#}", e);
+        return ExitCode::FAILURE;
+    }
+    let size = fs::metadata(&out).map_or(0, |metadata| metadata.len());
+    println!("packed {} file(s) into {} ({} bytes)", entries.len(), out.display(), size);
+    ExitCode::SUCCESS
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+            continue;
+        }
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        if extension.is_some_and(|e| EXTENSIONS.contains(&e.as_str())) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,352 @@
+//! Offline validation of the GLSL shaders under a directory, without a GPU.
+//!
+//! Usage: `cargo run --bin shader_check -- [DIR...]` (defaults to `resources/shaders`).
+//!
+//! Every `.vs`/`.vert`, `.fs`/`.frag` and `.comp` file is parsed and validated with naga,
+//! once per combination of the `HAS_*` defines `Material::defines` can emit (none included),
+//! so the `#ifdef` blocks of every material variant are checked too.
+//! Files sharing a stem (`basic_lighting.vs` + `basic_lighting.fs`) are treated as one program
+//! and the fragment inputs are checked against the vertex outputs by name and type.
+//! The process exits with 1 if anything fails, so it can run in CI.
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{Binding, Module, ScalarKind, ShaderStage, TypeInner};
+
+use rust_graphics_engine::material::Material;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut dirs: Vec<PathBuf> = std::env::args().skip(1).map(PathBuf::from).collect();
+    if dirs.is_empty() {
+        dirs.push(PathBuf::from("resources/shaders"));
+    }
+
+    let mut files = Vec::new();
+    for dir in &dirs {
+        if let Err(e) = collect_shaders(dir, &mut files) {
+            eprintln!("error: cannot read {}: {}", dir.display(), e);
+            return ExitCode::from(2);
+        }
+    }
+    files.sort();
+
+    let define_sets = define_sets();
+    let mut failures = 0;
+    // (stem, index into define_sets) -> (vertex module, fragment module), used for the
+    // interface check
+    let mut programs: BTreeMap<(PathBuf, usize), (Option<Module>, Option<Module>)> = BTreeMap::new();
+
+    for (path, stage) in files {
+        // stops at the first failing variant, so an error outside the #ifdefs is printed once
+        let result = define_sets.iter().enumerate().try_for_each(|(set, defines)| {
+            let module = check_file(&path, stage, defines).map_err(|message| (defines, message))?;
+            let program = programs.entry((path.with_extension(""), set)).or_default();
+            match stage {
+                ShaderStage::Vertex => program.0 = Some(module),
+                ShaderStage::Fragment => program.1 = Some(module),
+                _ => {}
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => println!("ok   {}", path.display()),
+            Err((defines, message)) => {
+                println!("FAIL {}{}\n{}", path.display(), describe(defines), message);
+                failures += 1;
+            }
+        }
+    }
+
+    let mut failed_programs = BTreeSet::new();
+    for ((stem, set), program) in &programs {
+        if failed_programs.contains(stem) {
+            continue;
+        }
+        if let (Some(vertex), Some(fragment)) = program
+            && let Err(message) = check_interface(vertex, fragment)
+        {
+            println!("FAIL {} (vertex -> fragment{})\n{}", stem.display(), describe(&define_sets[*set]), message);
+            failed_programs.insert(stem.clone());
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        println!("{} shader check(s) failed", failures);
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn collect_shaders(dir: &Path, files: &mut Vec<(PathBuf, ShaderStage)>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_shaders(&path, files)?;
+            continue;
+        }
+        let stage = match path.extension().and_then(|e| e.to_str()) {
+            Some("vs" | "vert") => ShaderStage::Vertex,
+            Some("fs" | "frag") => ShaderStage::Fragment,
+            Some("comp") => ShaderStage::Compute,
+            _ => continue,
+        };
+        files.push((path, stage));
+    }
+    Ok(())
+}
+
+/// Every subset of the defines `Material::defines` can emit, the empty one first.
+fn define_sets() -> Vec<Vec<String>> {
+    let defines: Vec<String> = Material::MAPS.iter().map(|slot| Material::define(slot)).collect();
+    (0..1u32 << defines.len())
+        .map(|mask| {
+            defines
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, define)| define.clone())
+                .collect()
+        })
+        .collect()
+}
+
+fn describe(defines: &[String]) -> String {
+    if defines.is_empty() { String::new() } else { format!(" with {}", defines.join(", ")) }
+}
+
+fn check_file(path: &Path, stage: ShaderStage, defines: &[String]) -> Result<Module, String> {
+    let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let source = to_vulkan_glsl(&source);
+
+    let mut options = Options::from(stage);
+    options.defines = defines.iter().map(|define| (define.clone(), String::new())).collect();
+    let module = Frontend::default()
+        .parse(&options, &source)
+        .map_err(|e| e.emit_to_string(&source))?;
+
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string_with_path(&source, &path.display().to_string()))?;
+
+    Ok(module)
+}
+
+/// Every fragment input must be written by the vertex stage with the same type.
+fn check_interface(vertex: &Module, fragment: &Module) -> Result<(), String> {
+    let outputs: Vec<(&str, &TypeInner)> = vertex
+        .entry_points
+        .iter()
+        .filter_map(|ep| ep.function.result.as_ref())
+        .flat_map(|result| match &vertex.types[result.ty].inner {
+            TypeInner::Struct { members, .. } => members
+                .iter()
+                .filter(|m| matches!(m.binding, Some(Binding::Location { .. })))
+                .filter_map(|m| Some((m.name.as_deref()?, &vertex.types[m.ty].inner)))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    for ep in &fragment.entry_points {
+        for arg in &ep.function.arguments {
+            if !matches!(arg.binding, Some(Binding::Location { .. })) {
+                continue;
+            }
+            let Some(name) = arg.name.as_deref() else { continue };
+            let ty = &fragment.types[arg.ty].inner;
+
+            match outputs.iter().find(|(output, _)| *output == name) {
+                None => errors.push(format!("  input `{}` is not written by the vertex shader", name)),
+                Some((_, output_ty)) if *output_ty != ty => errors.push(format!(
+                    "  input `{}` is {} in the fragment shader but {} in the vertex shader",
+                    name,
+                    glsl_type_name(ty),
+                    glsl_type_name(output_ty)
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+fn glsl_type_name(ty: &TypeInner) -> String {
+    let prefix = |kind| match kind {
+        ScalarKind::Sint => "i",
+        ScalarKind::Uint => "u",
+        ScalarKind::Bool => "b",
+        _ => "",
+    };
+    match ty {
+        TypeInner::Scalar(scalar) => match scalar.kind {
+            ScalarKind::Sint => "int".to_string(),
+            ScalarKind::Uint => "uint".to_string(),
+            ScalarKind::Bool => "bool".to_string(),
+            _ => "float".to_string(),
+        },
+        TypeInner::Vector { size, scalar } => format!("{}vec{}", prefix(scalar.kind), *size as u8),
+        TypeInner::Matrix { columns, rows, .. } => format!("mat{}x{}", *columns as u8, *rows as u8),
+        other => format!("{:?}", other),
+    }
+}
+
+/// naga's GLSL frontend only accepts Vulkan-flavoured GLSL 440+, while the engine's shaders are
+/// desktop GLSL 330. This rewrites the declarations naga rejects, keeping one output line per
+/// input line so that error messages point at the right line of the original file:
+///
+/// - `#version` is raised to 450;
+/// - `in`/`out` variables without a location get the next free one;
+/// - uniform blocks get a `binding`, and loose uniforms are wrapped in a block of their own;
+/// - `samplerXX` uniforms are split into a texture and a sampler, and their uses are rewritten
+///   as `samplerXX(name, name_sampler)`.
+///
+/// Declarations are expected one per line, which is how every shader in the repo is written.
+fn to_vulkan_glsl(source: &str) -> String {
+    let mut next_binding = 0;
+    let mut next_in = first_free_location(source, "in");
+    let mut next_out = first_free_location(source, "out");
+    let mut samplers: Vec<(String, String)> = Vec::new();
+    let mut depth = 0i32;
+    let mut lines = Vec::new();
+
+    for line in source.lines() {
+        let at_global_scope = depth == 0;
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#version") {
+            lines.push("#version 450 core".to_string());
+            continue;
+        }
+        if !at_global_scope {
+            lines.push(replace_samplers(line, &samplers));
+            continue;
+        }
+
+        let (layout, rest) = split_layout(trimmed);
+        let mut words = rest.split_whitespace();
+        let line = match words.next() {
+            Some(qualifier @ ("in" | "out")) if !has_qualifier(&layout, "location") => {
+                let counter = if qualifier == "in" { &mut next_in } else { &mut next_out };
+                let location = *counter;
+                *counter += 1;
+                format!("{} {}", with_qualifier(&layout, &format!("location = {}", location)), rest)
+            }
+            Some("uniform") if rest.contains('{') => {
+                next_binding += 1;
+                format!("{} {}", with_qualifier(&layout, &format!("binding = {}", next_binding - 1)), rest)
+            }
+            Some("uniform") => match words.next() {
+                Some(ty) if ty.starts_with("sampler") => {
+                    let name = rest
+                        .split_whitespace()
+                        .nth(2)
+                        .unwrap_or_default()
+                        .trim_end_matches(';')
+                        .to_string();
+                    samplers.push((name.clone(), ty.to_string()));
+                    next_binding += 2;
+                    format!(
+                        "layout(binding = {}) uniform {} {}; layout(binding = {}) uniform sampler {}_sampler;",
+                        next_binding - 2,
+                        ty.replacen("sampler", "texture", 1),
+                        name,
+                        next_binding - 1,
+                        name
+                    )
+                }
+                _ => {
+                    next_binding += 1;
+                    let binding = next_binding - 1;
+                    let declaration = rest.trim_start_matches("uniform").trim();
+                    format!("layout(binding = {}) uniform _Uniform{} {{ {} }};", binding, binding, declaration)
+                }
+            },
+            _ => replace_samplers(line, &samplers),
+        };
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// Splits a leading `layout (...)` qualifier off a declaration, returning its contents.
+fn split_layout(line: &str) -> (String, &str) {
+    if let Some(after) = line.strip_prefix("layout") {
+        let after = after.trim_start();
+        if let (Some(inner), Some(end)) = (after.strip_prefix('('), after.find(')')) {
+            return (inner[..end - 1].trim().to_string(), after[end + 1..].trim_start());
+        }
+    }
+    (String::new(), line)
+}
+
+fn has_qualifier(layout: &str, key: &str) -> bool {
+    layout.split(',').any(|q| q.split('=').next().unwrap_or_default().trim() == key)
+}
+
+fn with_qualifier(layout: &str, qualifier: &str) -> String {
+    if layout.is_empty() {
+        format!("layout({})", qualifier)
+    } else {
+        format!("layout({}, {})", layout, qualifier)
+    }
+}
+
+/// One past the highest explicit location used by `in`/`out` declarations, so that implicit
+/// ones never collide with explicit ones.
+fn first_free_location(source: &str, qualifier: &str) -> u32 {
+    source
+        .lines()
+        .filter_map(|line| {
+            let (layout, rest) = split_layout(line.trim_start());
+            if rest.split_whitespace().next() != Some(qualifier) {
+                return None;
+            }
+            layout
+                .split(',')
+                .filter_map(|q| q.split_once('='))
+                .find(|(key, _)| key.trim() == "location")
+                .and_then(|(_, value)| value.trim().parse::<u32>().ok())
+        })
+        .max()
+        .map_or(0, |location| location + 1)
+}
+
+fn replace_samplers(line: &str, samplers: &[(String, String)]) -> String {
+    let mut line = line.to_string();
+    for (name, ty) in samplers {
+        line = replace_identifier(&line, name, &format!("{}({}, {}_sampler)", ty, name, name));
+    }
+    line
+}
+
+/// Replaces whole-word occurrences of `ident` in `line`.
+fn replace_identifier(line: &str, ident: &str, replacement: &str) -> String {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(pos) = rest.find(ident) {
+        let before = rest[..pos].chars().next_back().or_else(|| result.chars().next_back());
+        let after = rest[pos + ident.len()..].chars().next();
+        result.push_str(&rest[..pos]);
+        if before.is_some_and(is_ident) || after.is_some_and(is_ident) {
+            result.push_str(ident);
+        } else {
+            result.push_str(replacement);
+        }
+        rest = &rest[pos + ident.len()..];
+    }
+    result.push_str(rest);
+    result
+}
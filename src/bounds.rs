@@ -0,0 +1,263 @@
+use glam::{Mat4, Vec3, Vec4};
+
+/// Axis-aligned bounding box. `Aabb::EMPTY` is the identity for `union`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, p| Aabb {
+            min: aabb.min.min(p),
+            max: aabb.max.max(p),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Box enclosing this one after `transform`, e.g. a model-space box moved to world space.
+    pub fn transformed(&self, transform: &Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        Aabb::from_points((0..8).map(|corner| {
+            let p = Vec3::new(
+                if corner & 1 == 0 { self.min.x } else { self.max.x },
+                if corner & 2 == 0 { self.min.y } else { self.max.y },
+                if corner & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            transform.transform_point3(p)
+        }))
+    }
+
+    /// Radius of the sphere centred on `center()` that encloses the box.
+    pub fn radius(&self) -> f32 {
+        self.half_extents().length()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    /// Sphere centred on the bounding box of `points`, just large enough to hold all of them.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points
+            .iter()
+            .map(|p| p.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Sphere { center, radius }
+    }
+}
+
+/// The six clip planes of a projection, as `(normal, distance)` with normals pointing inside.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a (model-)view-projection matrix (Gribb & Hartmann). Planes
+    /// come out in the space the matrix maps from: pass `projection * view * model` to test
+    /// model-space bounds directly.
+    ///
+    /// `zero_to_one` selects the clip depth range: `false` for OpenGL's default `-w..w`, `true`
+    /// for `0..w` as produced by reverse-Z projections.
+    pub fn from_matrix(m: &Mat4, zero_to_one: bool) -> Self {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        let (near, far) = if zero_to_one { (r2, r3 - r2) } else { (r3 + r2, r3 - r2) };
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, near, far].map(|p| {
+            let length = p.truncate().length();
+            // an infinite far plane degenerates to (0, 0, 0, w > 0): keep it as "always inside"
+            if length > f32::EPSILON { p / length } else { Vec4::new(0.0, 0.0, 0.0, 1.0) }
+        });
+        Frustum { planes }
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.truncate().dot(sphere.center) + p.w >= -sphere.radius)
+    }
+
+    /// Conservative: a box near a frustum corner may pass while being outside.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+        self.planes.iter().all(|p| {
+            let normal = p.truncate();
+            // the corner furthest along the plane normal
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + p.w >= 0.0
+        })
+    }
+}
+
+/// Half-line `origin + t * direction`, `t >= 0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Moves the ray into another space, e.g. world to model space with the inverse model
+    /// matrix. The direction is not renormalised, so hit distances stay in the original units.
+    pub fn transformed(&self, transform: &Mat4) -> Ray {
+        Ray {
+            origin: transform.transform_point3(self.origin),
+            direction: transform.transform_vector3(self.direction),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance to the first point of `aabb` along the ray (0 if the origin is inside).
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        if aabb.is_empty() {
+            return None;
+        }
+        // slab test, one axis at a time
+        let (mut t_near, mut t_far) = (0.0f32, f32::INFINITY);
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            let (min, max) = (aabb.min[axis], aabb.max[axis]);
+            if direction == 0.0 {
+                // parallel to the slab: the ray stays inside it or never enters it. Dividing
+                // would give 0 * inf = NaN for an origin on one of its planes
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - origin) / direction, (max - origin) / direction);
+            t_near = t_near.max(t0.min(t1));
+            t_far = t_far.min(t0.max(t1));
+        }
+        (t_near <= t_far).then_some(t_near)
+    }
+
+    /// Möller–Trumbore, hitting both faces. Returns the distance and the barycentric weights
+    /// of `a`, `b` and `c` at the hit point.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<(f32, Vec3)> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < 1e-8 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        (t >= 0.0).then_some((t, Vec3::new(1.0 - u - v, u, v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    const B: Vec3 = Vec3::new(2.0, 0.0, 0.0);
+    const C: Vec3 = Vec3::new(0.0, 2.0, 0.0);
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).abs().max_element() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn triangle_hit_distance_and_barycentrics() {
+        let ray = Ray { origin: Vec3::new(0.5, 0.5, 3.0), direction: Vec3::NEG_Z };
+        let (distance, barycentric) = ray.intersect_triangle(A, B, C).unwrap();
+        assert!((distance - 3.0).abs() < 1e-5);
+        assert_close(barycentric, Vec3::new(0.5, 0.25, 0.25));
+        let point = A * barycentric.x + B * barycentric.y + C * barycentric.z;
+        assert_close(point, ray.origin + ray.direction * distance);
+    }
+
+    #[test]
+    fn triangle_back_face_is_hit() {
+        let ray = Ray { origin: Vec3::new(1.0, 0.5, -2.0), direction: Vec3::Z };
+        let (distance, barycentric) = ray.intersect_triangle(A, B, C).unwrap();
+        assert!((distance - 2.0).abs() < 1e-5);
+        assert_close(barycentric, Vec3::new(0.25, 0.5, 0.25));
+    }
+
+    #[test]
+    fn triangle_misses() {
+        let outside = Ray { origin: Vec3::new(1.5, 1.5, 1.0), direction: Vec3::NEG_Z };
+        assert_eq!(outside.intersect_triangle(A, B, C), None);
+        let behind = Ray { origin: Vec3::new(0.5, 0.5, 1.0), direction: Vec3::Z };
+        assert_eq!(behind.intersect_triangle(A, B, C), None);
+        let parallel = Ray { origin: Vec3::new(-1.0, 0.5, 0.0), direction: Vec3::X };
+        assert_eq!(parallel.intersect_triangle(A, B, C), None);
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::from_points([Vec3::ZERO, Vec3::ONE])
+    }
+
+    #[test]
+    fn ray_hits_box_in_front() {
+        let ray = Ray { origin: Vec3::new(0.5, 0.5, -2.0), direction: Vec3::Z };
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(2.0));
+        let inside = Ray { origin: Vec3::splat(0.5), direction: Vec3::X };
+        assert_eq!(inside.intersect_aabb(&unit_box()), Some(0.0));
+        let behind = Ray { origin: Vec3::new(0.5, 0.5, 2.0), direction: Vec3::Z };
+        assert_eq!(behind.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn ray_along_a_face_plane() {
+        // origins exactly on the x = 0 and y = 1 planes, direction parallel to both
+        for origin in [Vec3::new(0.0, 0.5, -2.0), Vec3::new(0.5, 1.0, -2.0), Vec3::new(0.0, 1.0, -2.0)] {
+            let ray = Ray { origin, direction: Vec3::Z };
+            assert_eq!(ray.intersect_aabb(&unit_box()), Some(2.0), "{:?}", origin);
+        }
+        let outside = Ray { origin: Vec3::new(-0.001, 0.5, -2.0), direction: Vec3::Z };
+        assert_eq!(outside.intersect_aabb(&unit_box()), None);
+    }
+}
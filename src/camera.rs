@@ -1,12 +1,39 @@
-use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Deg, Angle};
+use cgmath::{InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rotation3, Vector3, Deg, Angle, ortho, perspective};
+
+use crate::bounds::{Frustum, Ray};
 
 const WORLD_UP: Vector3<f32> = Vector3::new(0.0, 1.0, 0.0);
 
+/// How the camera maps view space to clip space. The aspect ratio lives on the `Camera` so it
+/// can follow the window size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// `fov_y` in degrees. `far: None` puts the far plane at infinity.
+    Perspective { fov_y: f32, near: f32, far: Option<f32> },
+    /// `half_height` is half the visible height in world units; the width follows the aspect.
+    Orthographic { half_height: f32, near: f32, far: f32 },
+}
+
+/// How mouse and keyboard input turn the camera.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Yaw/pitch around the world up axis, pitch clamped to ±89°, no roll.
+    Fps,
+    /// Free 6-DOF flight driven by the orientation quaternion: rotations are relative to the
+    /// camera's own axes, so it can roll and loop.
+    Free,
+}
+
+#[derive(Clone, Debug)]
 pub struct Camera {
 
     pub position: Point3<f32>,
+    /// Kept in sync in both modes; in `Free` mode they describe the view direction only.
     pub yaw: f32,
     pub pitch: f32,
+    /// Rotation from camera space (looking down -Z, +Y up) to world space.
+    orientation: Quaternion<f32>,
+    mode: CameraMode,
 
     front: Vector3<f32>,
     right: Vector3<f32>,
@@ -15,6 +42,13 @@ pub struct Camera {
 
     pub speed: f32,
     pub sensitivity: f32,
+    /// Degrees per second for `CameraMovement::RollLeft`/`RollRight` in `Free` mode.
+    pub roll_speed: f32,
+
+    pub projection: Projection,
+    pub aspect: f32,
+    /// Maps the near plane to depth 1 and the far plane to 0. See `configure_depth`.
+    pub reverse_z: bool,
 }
 
 impl Camera {
@@ -25,11 +59,17 @@ impl Camera {
             position,
             yaw,
             pitch,
+            orientation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            mode: CameraMode::Fps,
             front: Vector3::new(0.0, 0.0, -1.0),
             right: Vector3::new(0.0, 0.0,  0.0),
             up   : Vector3::new(0.0, 1.0,  0.0),
             speed: 2.5,
             sensitivity: 0.1,
+            roll_speed: 90.0,
+            projection: Projection::Perspective { fov_y: 45.0, near: 0.1, far: Some(100.0) },
+            aspect: 800.0 / 600.0,
+            reverse_z: false,
         };
         camera.update_vectors();
         camera
@@ -39,16 +79,135 @@ impl Camera {
         Matrix4::look_at_rh(self.position, self.position + self.front, self.up)
     }
 
+    pub fn get_projection_matrix(&self) -> Matrix4<f32> {
+        match (self.projection, self.reverse_z) {
+            (Projection::Perspective { fov_y, near, far: Some(far) }, false) => {
+                perspective(Deg(fov_y), self.aspect, near, far)
+            }
+            (Projection::Perspective { fov_y, near, far: None }, false) => {
+                let f = 1.0 / Deg(fov_y / 2.0).tan();
+                Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, -1.0, -1.0,
+                    0.0, 0.0, -2.0 * near, 0.0,
+                )
+            }
+            (Projection::Perspective { fov_y, near, far }, true) => {
+                let f = 1.0 / Deg(fov_y / 2.0).tan();
+                // depth = near / -z_view for an infinite far plane
+                let (z_scale, z_offset) = match far {
+                    Some(far) => (near / (far - near), far * near / (far - near)),
+                    None => (0.0, near),
+                };
+                Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, z_scale, -1.0,
+                    0.0, 0.0, z_offset, 0.0,
+                )
+            }
+            (Projection::Orthographic { half_height, near, far }, false) => {
+                let half_width = half_height * self.aspect;
+                ortho(-half_width, half_width, -half_height, half_height, near, far)
+            }
+            (Projection::Orthographic { half_height, near, far }, true) => {
+                let half_width = half_height * self.aspect;
+                Matrix4::new(
+                    1.0 / half_width, 0.0, 0.0, 0.0,
+                    0.0, 1.0 / half_height, 0.0, 0.0,
+                    0.0, 0.0, 1.0 / (far - near), 0.0,
+                    0.0, 0.0, far / (far - near), 1.0,
+                )
+            }
+        }
+    }
+
+    pub fn get_view_projection_matrix(&self) -> Matrix4<f32> {
+        self.get_projection_matrix() * self.get_view_matrix()
+    }
+
+    /// Clip planes of the camera in the space `model` maps from, for culling that space's
+    /// bounds directly. Pass the identity for world-space planes.
+    pub fn get_frustum(&self, model: &Matrix4<f32>) -> Frustum {
+        let mvp: [[f32; 4]; 4] = (self.get_view_projection_matrix() * model).into();
+        Frustum::from_matrix(&glam::Mat4::from_cols_array_2d(&mvp), self.reverse_z)
+    }
+
+    /// World-space ray through the cursor at (`x`, `y`) pixels from the top-left corner of a
+    /// `width` x `height` viewport. The ray starts on the near plane, so hit distances are
+    /// measured from there.
+    pub fn screen_to_ray(&self, x: f32, y: f32, width: f32, height: f32) -> Ray {
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+        let view_projection: [[f32; 4]; 4] = self.get_view_projection_matrix().into();
+        let inverse = glam::Mat4::from_cols_array_2d(&view_projection).inverse();
+
+        // a second point halfway in NDC depth stays finite even with an infinite far plane
+        let (near_z, mid_z) = if self.reverse_z { (1.0, 0.5) } else { (-1.0, 0.0) };
+        let near = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, near_z));
+        let mid = inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, mid_z));
+
+        Ray { origin: near, direction: (mid - near).normalize() }
+    }
+
+    /// World position of window pixel `(x, y)` at window-space `depth` (`0..1`), e.g. as read
+    /// back by `IdBuffer`. Assumes a `0..1` clip depth range when `reverse_z` is set, as
+    /// `configure_depth` sets up.
+    pub fn screen_to_world(&self, x: f32, y: f32, depth: f32, width: f32, height: f32) -> glam::Vec3 {
+        let ndc_x = 2.0 * x / width - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height;
+        let ndc_z = if self.reverse_z { depth } else { 2.0 * depth - 1.0 };
+        let view_projection: [[f32; 4]; 4] = self.get_view_projection_matrix().into();
+        let inverse = glam::Mat4::from_cols_array_2d(&view_projection).inverse();
+        inverse.project_point3(glam::Vec3::new(ndc_x, ndc_y, ndc_z))
+    }
+
+    /// Keeps the aspect ratio in step with the framebuffer. Call it on `WindowEvent::Resized`.
+    pub fn on_resize(&mut self, width: u32, height: u32) {
+        // a minimised window reports 0x0
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    /// Sets the depth clear value, depth test and (where `glClipControl` exists) the clip-space
+    /// depth range that match `reverse_z`. Must be called with the GL context current.
+    pub fn configure_depth(&self) {
+        unsafe {
+            if self.reverse_z {
+                if gl::ClipControl::is_loaded() {
+                    gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+                }
+                gl::ClearDepth(0.0);
+                gl::DepthFunc(gl::GREATER);
+            } else {
+                if gl::ClipControl::is_loaded() {
+                    gl::ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
+                }
+                gl::ClearDepth(1.0);
+                gl::DepthFunc(gl::LESS);
+            }
+        }
+    }
+
     pub fn process_keyboard(&mut self, direction: CameraMovement, delta_time: f32) {
 
         let velocity = self.speed * delta_time;
+        // "up" follows the camera when flying freely, the world otherwise
+        let up = match self.mode {
+            CameraMode::Fps => WORLD_UP,
+            CameraMode::Free => self.up,
+        };
         match direction {
             CameraMovement::Forward => self.position += self.front * velocity,
             CameraMovement::Backward => self.position -= self.front * velocity,
             CameraMovement::Left => self.position -= self.right * velocity,
             CameraMovement::Right => self.position += self.right * velocity,
-            CameraMovement::Up => self.position += WORLD_UP * velocity,
-            CameraMovement::Down => self.position -= WORLD_UP * velocity,
+            CameraMovement::Up => self.position += up * velocity,
+            CameraMovement::Down => self.position -= up * velocity,
+            CameraMovement::RollLeft => self.roll(-self.roll_speed * delta_time),
+            CameraMovement::RollRight => self.roll(self.roll_speed * delta_time),
         }
 
     }
@@ -58,25 +217,132 @@ impl Camera {
         x_offset *= self.sensitivity;
         y_offset *= self.sensitivity;
 
-        self.yaw += x_offset;
-        self.pitch += y_offset;
-        
-        if self.pitch >  89.0 { self.pitch =  89.0; }
-        if self.pitch < -89.0 { self.pitch = -89.0}
+        match self.mode {
+            CameraMode::Fps => {
+                self.yaw += x_offset;
+                self.pitch += y_offset;
+
+                self.pitch = self.pitch.clamp(-89.0, 89.0);
+            }
+            CameraMode::Free => {
+                // local axes: turning right is a negative turn around +Y, looking up a positive
+                // turn around +X
+                self.orientation = self.orientation
+                    * Quaternion::from_angle_y(Deg(-x_offset))
+                    * Quaternion::from_angle_x(Deg(y_offset));
+            }
+        }
 
         self.update_vectors();
     }
-    
+
+    /// Rolls around the view direction; positive is clockwise as seen by the camera. Ignored in
+    /// `Fps` mode, which has no roll.
+    pub fn roll(&mut self, degrees: f32) {
+        if self.mode == CameraMode::Free {
+            self.orientation = self.orientation * Quaternion::from_angle_z(Deg(-degrees));
+            self.update_vectors();
+        }
+    }
+
+    /// Turns the camera towards `target` without moving it. Clears any roll.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = (target - self.position).normalize();
+        self.yaw = direction.z.atan2(direction.x).to_degrees();
+        self.pitch = direction.y.clamp(-1.0, 1.0).asin().to_degrees().clamp(-89.0, 89.0);
+        self.orientation = Self::orientation_from_euler(self.yaw, self.pitch);
+        self.update_vectors();
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches modes keeping the view direction. Going back to `Fps` drops the roll and clamps
+    /// the pitch.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Fps {
+            self.pitch = self.pitch.clamp(-89.0, 89.0);
+            self.orientation = Self::orientation_from_euler(self.yaw, self.pitch);
+        }
+        self.mode = mode;
+        self.update_vectors();
+    }
+
+    pub fn orientation(&self) -> Quaternion<f32> {
+        self.orientation
+    }
+
+    /// Sets the full orientation and switches to `Free` mode, where it is meaningful.
+    pub fn set_orientation(&mut self, orientation: Quaternion<f32>) {
+        self.orientation = orientation.normalize();
+        self.mode = CameraMode::Free;
+        self.update_vectors();
+    }
+
+    /// The camera a fraction `alpha` of the way from `self` to `next`, for rendering between
+    /// two fixed simulation ticks. Takes everything but the pose from `next`.
+    pub fn interpolated(&self, next: &Camera, alpha: f32) -> Camera {
+        let mut camera = next.clone();
+        camera.position = self.position + (next.position - self.position) * alpha;
+        match (self.mode, next.mode) {
+            (CameraMode::Fps, CameraMode::Fps) => {
+                camera.yaw = self.yaw + (next.yaw - self.yaw) * alpha;
+                camera.pitch = self.pitch + (next.pitch - self.pitch) * alpha;
+            }
+            _ => camera.orientation = self.orientation.slerp(next.orientation, alpha),
+        }
+        camera.update_vectors();
+        camera
+    }
+
+    pub fn front(&self) -> Vector3<f32> {
+        self.front
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.right
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
+    fn orientation_from_euler(yaw: f32, pitch: f32) -> Quaternion<f32> {
+        let front = Self::front_from_euler(yaw, pitch);
+        let right = front.cross(WORLD_UP).normalize();
+        let up = right.cross(front).normalize();
+        Quaternion::from(Matrix3::from_cols(right, up, -front)).normalize()
+    }
+
+    fn front_from_euler(yaw: f32, pitch: f32) -> Vector3<f32> {
+        Vector3::new(
+            Deg(yaw).cos() * Deg(pitch).cos(),
+            Deg(pitch).sin(),
+            Deg(yaw).sin() * Deg(pitch).cos(),
+        )
+        .normalize()
+    }
+
     fn update_vectors(&mut self) {
-            let front = Vector3::new(
-                Deg(self.yaw).cos() * Deg(self.pitch).cos(),
-                Deg(self.pitch).sin(),
-                Deg(self.yaw).sin() * Deg(self.pitch).cos(),
-            );
-            self.front = front.normalize();
-            self.right = self.front.cross(WORLD_UP).normalize();
-            self.up = self.right.cross(self.front).normalize();
+        match self.mode {
+            CameraMode::Fps => {
+                self.front = Self::front_from_euler(self.yaw, self.pitch);
+                self.right = self.front.cross(WORLD_UP).normalize();
+                self.up = self.right.cross(self.front).normalize();
+                self.orientation = Self::orientation_from_euler(self.yaw, self.pitch);
+            }
+            CameraMode::Free => {
+                // renormalise so rounding errors don't accumulate over many small rotations
+                self.orientation = self.orientation.normalize();
+                self.front = self.orientation * Vector3::new(0.0, 0.0, -1.0);
+                self.right = self.orientation * Vector3::new(1.0, 0.0, 0.0);
+                self.up = self.orientation * Vector3::new(0.0, 1.0, 0.0);
+                self.yaw = self.front.z.atan2(self.front.x).to_degrees();
+                self.pitch = self.front.y.clamp(-1.0, 1.0).asin().to_degrees();
+            }
         }
+    }
 }
 
 
@@ -88,5 +354,48 @@ pub enum CameraMovement {
     Right,
     Up,
     Down,
+    RollLeft,
+    RollRight,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_along_front(camera: &Camera) {
+        let ray = camera.screen_to_ray(400.0, 300.0, 800.0, 600.0);
+        let front = glam::Vec3::new(camera.front().x, camera.front().y, camera.front().z);
+        assert!((ray.direction - front).length() < 1e-4, "{:?} != {:?}", ray.direction, front);
+        // the ray starts on the near plane, straight ahead of the camera
+        let position = glam::Vec3::new(camera.position.x, camera.position.y, camera.position.z);
+        let offset = ray.origin - position;
+        assert!(offset.cross(front).length() < 1e-4, "origin {:?} is off axis", ray.origin);
+        assert!((offset.dot(front) - 0.1).abs() < 1e-3, "origin {:?} is not on the near plane", ray.origin);
+    }
+
+    #[test]
+    fn centre_ray_follows_front_in_perspective() {
+        let mut camera = Camera::new(Point3::new(1.0, 2.0, 3.0), -60.0, 20.0);
+        assert_along_front(&camera);
+        camera.reverse_z = true;
+        camera.projection = Projection::Perspective { fov_y: 60.0, near: 0.1, far: None };
+        assert_along_front(&camera);
+    }
+
+    #[test]
+    fn centre_ray_follows_front_in_orthographic() {
+        let mut camera = Camera::new(Point3::new(-2.0, 0.5, 4.0), 30.0, -45.0);
+        camera.projection = Projection::Orthographic { half_height: 5.0, near: 0.1, far: 50.0 };
+        assert_along_front(&camera);
+        camera.reverse_z = true;
+        assert_along_front(&camera);
+
+        // off-centre rays stay parallel, shifted across the view
+        let corner = camera.screen_to_ray(0.0, 0.0, 800.0, 600.0);
+        let centre = camera.screen_to_ray(400.0, 300.0, 800.0, 600.0);
+        assert!((corner.direction - centre.direction).length() < 1e-4);
+        // half extents 5 x 20/3, so the corner is 25/3 from the centre
+        assert!(((corner.origin - centre.origin).length() - 25.0 / 3.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,456 @@
+use cgmath::{InnerSpace, Matrix3, Point3, Quaternion, Vector3, EuclideanSpace};
+
+use crate::camera::Camera;
+use crate::vfs;
+
+use std::fmt::Write as _;
+
+/// One pose of a camera path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the path.
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub orientation: Quaternion<f32>,
+    /// When set, the camera faces this point at the key instead of using `orientation`.
+    pub look_at: Option<Point3<f32>>,
+    /// Incoming and outgoing Bézier control points. Without them `Bezier` falls back to the
+    /// Catmull-Rom tangents, giving the same curve.
+    pub handles: Option<(Point3<f32>, Point3<f32>)>,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, position: Point3<f32>, orientation: Quaternion<f32>) -> Self {
+        Self { time, position, orientation, look_at: None, handles: None }
+    }
+
+    /// Records the current pose of `camera`.
+    pub fn from_camera(time: f32, camera: &Camera) -> Self {
+        Self::new(time, camera.position, camera.orientation())
+    }
+
+    pub fn looking_at(mut self, target: Point3<f32>) -> Self {
+        self.look_at = Some(target);
+        self
+    }
+
+    fn facing(&self) -> Quaternion<f32> {
+        match self.look_at {
+            Some(target) => look_rotation(target - self.position),
+            None => self.orientation,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionInterpolation {
+    CatmullRom,
+    Bezier,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrientationInterpolation {
+    Slerp,
+    /// Spherical cubic interpolation: no angular-velocity jumps at the keys.
+    Squad,
+}
+
+/// Remaps playback time over the whole path, so the camera can ease in and out of the flight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A keyframed camera flight. `sample` is a pure function of time, so the same path and time
+/// always give the same pose.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    pub position_mode: PositionInterpolation,
+    pub orientation_mode: OrientationInterpolation,
+    pub easing: Easing,
+    pub looping: bool,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraPath {
+
+    pub fn new() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            position_mode: PositionInterpolation::CatmullRom,
+            orientation_mode: OrientationInterpolation::Squad,
+            easing: Easing::Linear,
+            looping: false,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Inserts `keyframe` in time order, replacing a key at the same time.
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        match self.keyframes.binary_search_by(|k| k.time.total_cmp(&keyframe.time)) {
+            Ok(i) => self.keyframes[i] = keyframe,
+            Err(i) => self.keyframes.insert(i, keyframe),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    pub fn duration(&self) -> f32 {
+        match (self.keyframes.first(), self.keyframes.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    /// Position and orientation at `time` seconds. `None` for an empty path.
+    pub fn sample(&self, time: f32) -> Option<(Point3<f32>, Quaternion<f32>)> {
+        let first = self.keyframes.first()?;
+        let duration = self.duration();
+        if self.keyframes.len() == 1 || duration <= 0.0 {
+            return Some((first.position, first.facing()));
+        }
+
+        let local = if self.looping { time.rem_euclid(duration) } else { time.clamp(0.0, duration) };
+        let time = first.time + self.easing.apply(local / duration) * duration;
+
+        let i = self
+            .keyframes
+            .partition_point(|k| k.time <= time)
+            .clamp(1, self.keyframes.len() - 1)
+            - 1;
+        let (k1, k2) = (&self.keyframes[i], &self.keyframes[i + 1]);
+        let t = ((time - k1.time) / (k2.time - k1.time)).clamp(0.0, 1.0);
+
+        let position = self.sample_position(i, t);
+        let orientation = match (k1.look_at, k2.look_at) {
+            (Some(a), Some(b)) => look_rotation(a + (b - a) * t - position),
+            _ => self.sample_orientation(i, t),
+        };
+        Some((position, orientation))
+    }
+
+    /// Places `camera` at the pose for `time`, switching it to free-flight mode so the
+    /// orientation (including roll) is used as is.
+    pub fn apply(&self, camera: &mut Camera, time: f32) {
+        if let Some((position, orientation)) = self.sample(time) {
+            camera.position = position;
+            camera.set_orientation(orientation);
+        }
+    }
+
+    fn key(&self, i: isize) -> &Keyframe {
+        let last = self.keyframes.len() as isize - 1;
+        &self.keyframes[i.clamp(0, last) as usize]
+    }
+
+    fn tangent(&self, i: usize) -> Vector3<f32> {
+        let i = i as isize;
+        (self.key(i + 1).position - self.key(i - 1).position) * 0.5
+    }
+
+    fn sample_position(&self, i: usize, t: f32) -> Point3<f32> {
+        let (p1, p2) = (self.keyframes[i].position, self.keyframes[i + 1].position);
+        let (c1, c2) = match self.position_mode {
+            PositionInterpolation::CatmullRom => (p1 + self.tangent(i) / 3.0, p2 - self.tangent(i + 1) / 3.0),
+            PositionInterpolation::Bezier => (
+                self.keyframes[i].handles.map_or(p1 + self.tangent(i) / 3.0, |(_, out)| out),
+                self.keyframes[i + 1].handles.map_or(p2 - self.tangent(i + 1) / 3.0, |(incoming, _)| incoming),
+            ),
+        };
+
+        // Catmull-Rom written as a Bézier: both share the same evaluation
+        let u = 1.0 - t;
+        Point3::from_vec(
+            p1.to_vec() * (u * u * u)
+                + c1.to_vec() * (3.0 * u * u * t)
+                + c2.to_vec() * (3.0 * u * t * t)
+                + p2.to_vec() * (t * t * t),
+        )
+    }
+
+    fn sample_orientation(&self, i: usize, t: f32) -> Quaternion<f32> {
+        let i = i as isize;
+        let q1 = self.key(i).facing();
+        let q2 = same_hemisphere(q1, self.key(i + 1).facing());
+
+        match self.orientation_mode {
+            OrientationInterpolation::Slerp => slerp(q1, q2, t),
+            OrientationInterpolation::Squad => {
+                let q0 = same_hemisphere(q1, self.key(i - 1).facing());
+                let q3 = same_hemisphere(q2, self.key(i + 2).facing());
+                let s1 = squad_control(q0, q1, q2);
+                let s2 = squad_control(q1, q2, q3);
+                slerp(slerp(q1, q2, t), slerp(s1, s2, t), 2.0 * t * (1.0 - t))
+            }
+        }
+    }
+
+    /// Writes the path as text, one line per setting and per key:
+    ///
+    /// ```text
+    /// position catmull-rom
+    /// orientation squad
+    /// easing ease-in-out
+    /// loop false
+    /// key <time> <px> <py> <pz> <qw> <qx> <qy> <qz> [target <x> <y> <z>] [handles <in xyz> <out xyz>]
+    /// ```
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# camera path\n");
+        let position = match self.position_mode {
+            PositionInterpolation::CatmullRom => "catmull-rom",
+            PositionInterpolation::Bezier => "bezier",
+        };
+        let orientation = match self.orientation_mode {
+            OrientationInterpolation::Slerp => "slerp",
+            OrientationInterpolation::Squad => "squad",
+        };
+        let easing = match self.easing {
+            Easing::Linear => "linear",
+            Easing::EaseIn => "ease-in",
+            Easing::EaseOut => "ease-out",
+            Easing::EaseInOut => "ease-in-out",
+        };
+        let _ = writeln!(text, "position {}\norientation {}\neasing {}\nloop {}", position, orientation, easing, self.looping);
+
+        for k in &self.keyframes {
+            let (p, q) = (k.position, k.orientation);
+            let _ = write!(text, "key {} {} {} {} {} {} {} {}", k.time, p.x, p.y, p.z, q.s, q.v.x, q.v.y, q.v.z);
+            if let Some(t) = k.look_at {
+                let _ = write!(text, " target {} {} {}", t.x, t.y, t.z);
+            }
+            if let Some((a, b)) = k.handles {
+                let _ = write!(text, " handles {} {} {} {} {} {}", a.x, a.y, a.z, b.x, b.y, b.z);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> anyhow::Result<Self> {
+        let mut path = CameraPath::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let value = words.next();
+            let bad = || anyhow::anyhow!("camera path line {}: cannot parse `{}`", number + 1, line);
+
+            match (keyword, value) {
+                ("position", Some("catmull-rom")) => path.position_mode = PositionInterpolation::CatmullRom,
+                ("position", Some("bezier")) => path.position_mode = PositionInterpolation::Bezier,
+                ("orientation", Some("slerp")) => path.orientation_mode = OrientationInterpolation::Slerp,
+                ("orientation", Some("squad")) => path.orientation_mode = OrientationInterpolation::Squad,
+                ("easing", Some("linear")) => path.easing = Easing::Linear,
+                ("easing", Some("ease-in")) => path.easing = Easing::EaseIn,
+                ("easing", Some("ease-out")) => path.easing = Easing::EaseOut,
+                ("easing", Some("ease-in-out")) => path.easing = Easing::EaseInOut,
+                ("loop", Some(value)) => path.looping = value.parse().map_err(|_| bad())?,
+                ("key", Some(time)) => {
+                    let rest: Vec<&str> = words.collect();
+                    let numbers = |from: usize, count: usize| -> anyhow::Result<Vec<f32>> {
+                        rest.get(from..from + count)
+                            .ok_or_else(bad)?
+                            .iter()
+                            .map(|w| w.parse().map_err(|_| bad()))
+                            .collect()
+                    };
+                    let time: f32 = time.parse().map_err(|_| bad())?;
+                    let v = numbers(0, 7)?;
+                    let mut key = Keyframe::new(
+                        time,
+                        Point3::new(v[0], v[1], v[2]),
+                        Quaternion::new(v[3], v[4], v[5], v[6]),
+                    );
+                    let mut i = 7;
+                    while i < rest.len() {
+                        match rest[i] {
+                            "target" => {
+                                let t = numbers(i + 1, 3)?;
+                                key.look_at = Some(Point3::new(t[0], t[1], t[2]));
+                                i += 4;
+                            }
+                            "handles" => {
+                                let h = numbers(i + 1, 6)?;
+                                key.handles = Some((Point3::new(h[0], h[1], h[2]), Point3::new(h[3], h[4], h[5])));
+                                i += 7;
+                            }
+                            _ => return Err(bad()),
+                        }
+                    }
+                    path.add_keyframe(key);
+                }
+                _ => return Err(bad()),
+            }
+        }
+
+        Ok(path)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        vfs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Self::from_text(&vfs::read_to_string(path)?)
+    }
+}
+
+/// Orientation of a camera looking along `direction` with the world up axis on top.
+fn look_rotation(direction: Vector3<f32>) -> Quaternion<f32> {
+    let front = direction.normalize();
+    let up_hint = if front.y.abs() > 0.999 { Vector3::unit_z() } else { Vector3::unit_y() };
+    let right = front.cross(up_hint).normalize();
+    let up = right.cross(front);
+    Quaternion::from(Matrix3::from_cols(right, up, -front)).normalize()
+}
+
+/// `q` or `-q`, whichever is closer to `reference`; both are the same rotation.
+fn same_hemisphere(reference: Quaternion<f32>, q: Quaternion<f32>) -> Quaternion<f32> {
+    if reference.dot(q) < 0.0 { -q } else { q }
+}
+
+fn slerp(a: Quaternion<f32>, b: Quaternion<f32>, t: f32) -> Quaternion<f32> {
+    let b = same_hemisphere(a, b);
+    let dot = a.dot(b).min(1.0);
+    if dot > 0.9995 {
+        // nearly parallel: nlerp avoids dividing by sin(θ) ≈ 0
+        return (a + (b - a) * t).normalize();
+    }
+    let theta = dot.acos();
+    (a * ((1.0 - t) * theta).sin() + b * (t * theta).sin()) / theta.sin()
+}
+
+/// Inner control quaternion of key `q1` for squad, from its neighbours.
+fn squad_control(q0: Quaternion<f32>, q1: Quaternion<f32>, q2: Quaternion<f32>) -> Quaternion<f32> {
+    let inv = q1.conjugate();
+    let sum = quat_log(inv * q2) + quat_log(inv * q0);
+    (q1 * quat_exp(sum * -0.25)).normalize()
+}
+
+fn quat_log(q: Quaternion<f32>) -> Quaternion<f32> {
+    let len = q.v.magnitude();
+    if len < 1e-6 {
+        return Quaternion::new(0.0, 0.0, 0.0, 0.0);
+    }
+    let angle = len.atan2(q.s);
+    Quaternion::from_sv(0.0, q.v * (angle / len))
+}
+
+fn quat_exp(q: Quaternion<f32>) -> Quaternion<f32> {
+    let angle = q.v.magnitude();
+    if angle < 1e-6 {
+        return Quaternion::new(1.0, 0.0, 0.0, 0.0);
+    }
+    Quaternion::from_sv(angle.cos(), q.v * (angle.sin() / angle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation3};
+
+    fn path() -> CameraPath {
+        let mut path = CameraPath::new();
+        path.add_keyframe(Keyframe::new(0.0, Point3::new(0.0, 0.0, 0.0), Quaternion::from_angle_y(Deg(0.0))));
+        path.add_keyframe(Keyframe::new(2.0, Point3::new(4.0, 0.0, 0.0), Quaternion::from_angle_y(Deg(90.0))));
+        path.add_keyframe(Keyframe::new(1.0, Point3::new(2.0, 1.0, 0.0), Quaternion::from_angle_y(Deg(45.0))));
+        path
+    }
+
+    fn close(a: Point3<f32>, b: Point3<f32>) -> bool {
+        (a - b).magnitude() < 1e-4
+    }
+
+    #[test]
+    fn keyframes_are_kept_in_time_order() {
+        let times: Vec<f32> = path().keyframes().iter().map(|k| k.time).collect();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+        assert_eq!(path().duration(), 2.0);
+    }
+
+    #[test]
+    fn sample_passes_through_the_keys() {
+        for mode in [PositionInterpolation::CatmullRom, PositionInterpolation::Bezier] {
+            let mut path = path();
+            path.position_mode = mode;
+            for key in path.keyframes() {
+                let (position, orientation) = path.sample(key.time).unwrap();
+                assert!(close(position, key.position), "{:?} at {}", mode, key.time);
+                assert!(orientation.dot(key.orientation).abs() > 0.9999);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_clamps_or_loops_outside_the_path() {
+        let mut path = path();
+        assert!(close(path.sample(-1.0).unwrap().0, Point3::new(0.0, 0.0, 0.0)));
+        assert!(close(path.sample(5.0).unwrap().0, Point3::new(4.0, 0.0, 0.0)));
+
+        path.looping = true;
+        assert!(close(path.sample(3.0).unwrap().0, Point3::new(2.0, 1.0, 0.0)));
+        assert!(CameraPath::new().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut path = path();
+        path.position_mode = PositionInterpolation::Bezier;
+        path.orientation_mode = OrientationInterpolation::Slerp;
+        path.easing = Easing::EaseInOut;
+        path.looping = true;
+        path.add_keyframe(
+            Keyframe { handles: Some((Point3::new(0.5, 0.25, 0.0), Point3::new(1.5, 0.0, -0.1))), ..path.keyframes()[1] }
+                .looking_at(Point3::new(0.0, 0.0, -3.0)),
+        );
+        assert_eq!(CameraPath::from_text(&path.to_text()).unwrap(), path);
+    }
+
+    #[test]
+    fn from_text_rejects_malformed_lines() {
+        assert!(CameraPath::from_text("key 0 1 2").is_err());
+        assert!(CameraPath::from_text("easing sideways").is_err());
+        assert!(CameraPath::from_text("key 0 0 0 0 1 0 0 0 bogus").is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let file = std::env::temp_dir().join(format!("camera_path_test_{}.txt", std::process::id()));
+        let file = file.to_str().unwrap();
+        path().save(file).unwrap();
+        let loaded = CameraPath::load(file);
+        let _ = std::fs::remove_file(file);
+        assert_eq!(loaded.unwrap(), path());
+    }
+}
@@ -0,0 +1,24 @@
+//! Conversions between sRGB-encoded values (what image files, colour pickers and MTL files
+//! hold) and linear values (what lighting maths needs).
+
+/// Decodes one sRGB channel in `0..1` to linear light.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes one linear channel in `0..1` as sRGB.
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear3(c: cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+    c.map(srgb_to_linear)
+}
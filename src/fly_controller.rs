@@ -0,0 +1,59 @@
+use crate::camera::{Camera, CameraMovement};
+use crate::input::Input;
+
+/// Moves a `Camera` from named input actions instead of raw keys, so the bindings live in
+/// the `InputMap`. Reads the actions and axes named by the constants below.
+pub struct FlyController {
+    /// Turns the camera with the `look_x`/`look_y` axes. Off while the cursor is free.
+    pub mouse_look: bool,
+}
+
+impl FlyController {
+
+    pub const FORWARD: &'static str = "move_forward";
+    pub const BACKWARD: &'static str = "move_backward";
+    pub const LEFT: &'static str = "move_left";
+    pub const RIGHT: &'static str = "move_right";
+    pub const UP: &'static str = "move_up";
+    pub const DOWN: &'static str = "move_down";
+    pub const ROLL_LEFT: &'static str = "roll_left";
+    pub const ROLL_RIGHT: &'static str = "roll_right";
+    /// Axes in mouse pixels; `look_y` is positive when looking up.
+    pub const LOOK_X: &'static str = "look_x";
+    pub const LOOK_Y: &'static str = "look_y";
+
+    pub fn new() -> Self {
+        Self { mouse_look: true }
+    }
+
+    pub fn update(&self, input: &Input, camera: &mut Camera, delta_time: f32) {
+        let movements = [
+            (Self::FORWARD, CameraMovement::Forward),
+            (Self::BACKWARD, CameraMovement::Backward),
+            (Self::LEFT, CameraMovement::Left),
+            (Self::RIGHT, CameraMovement::Right),
+            (Self::UP, CameraMovement::Up),
+            (Self::DOWN, CameraMovement::Down),
+            (Self::ROLL_LEFT, CameraMovement::RollLeft),
+            (Self::ROLL_RIGHT, CameraMovement::RollRight),
+        ];
+        for (action, movement) in movements {
+            if input.held(action) {
+                camera.process_keyboard(movement, delta_time);
+            }
+        }
+
+        if self.mouse_look {
+            let (x, y) = (input.axis(Self::LOOK_X), input.axis(Self::LOOK_Y));
+            if x != 0.0 || y != 0.0 {
+                camera.process_mouse(x, y);
+            }
+        }
+    }
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,111 @@
+//! Owned GL object names that delete themselves when dropped.
+//!
+//! Dropping GL objects needs the context that created them to still be current: after it is
+//! destroyed (or replaced) the ids are meaningless and the calls may crash. Each object
+//! remembers which context it was created under (`begin_context` / `end_context`, called by
+//! `app::run`), and is only deleted while that same context is live. Otherwise it is left
+//! for the driver, which frees everything with the context anyway.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+thread_local! {
+    // GL contexts are current on one thread; 0 means none is live on this one
+    static CURRENT_CONTEXT: Cell<u64> = const { Cell::new(0) };
+}
+
+// shared by every thread, so contexts created on different threads never get the same id
+static NEXT_CONTEXT: AtomicU64 = AtomicU64::new(1);
+
+/// Marks a newly created context as current on this thread. Objects created from now on
+/// belong to it. Loops that do not go through `app::run` call this after `gl::load_with`;
+/// without it, objects are never deleted.
+pub fn begin_context() {
+    let context = NEXT_CONTEXT.fetch_add(1, Ordering::Relaxed);
+    CURRENT_CONTEXT.with(|current| current.set(context));
+}
+
+/// Marks the current context as gone: objects dropped afterwards skip their GL calls.
+pub fn end_context() {
+    CURRENT_CONTEXT.with(|current| current.set(0));
+}
+
+/// Whether a context registered with `begin_context` is live on this thread.
+pub fn has_context() -> bool {
+    current_context() != 0
+}
+
+fn current_context() -> u64 {
+    CURRENT_CONTEXT.with(|current| current.get())
+}
+
+macro_rules! gl_object {
+    ($(#[$doc:meta])* $name:ident, $gen:ident, $delete:ident) => {
+        gl_object!(
+            $(#[$doc])* $name,
+            create: { let mut id = 0; gl::$gen(1, &mut id); id },
+            delete(id): { gl::$delete(1, &id) }
+        );
+    };
+    ($(#[$doc:meta])* $name:ident, create: $create:block, delete($id:ident): $delete:block) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub struct $name {
+            id: u32,
+            context: u64,
+        }
+
+        impl $name {
+            pub fn new() -> Self {
+                let id = unsafe { $create };
+                Self { id, context: current_context() }
+            }
+
+            pub fn id(&self) -> u32 {
+                self.id
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                if self.context != 0 && self.context == current_context() {
+                    let $id = self.id;
+                    unsafe { $delete }
+                }
+            }
+        }
+    };
+}
+
+gl_object!(
+    /// Buffer object (vertex, index, uniform...).
+    GlBuffer, GenBuffers, DeleteBuffers
+);
+gl_object!(
+    /// Vertex array object.
+    GlVertexArray, GenVertexArrays, DeleteVertexArrays
+);
+gl_object!(
+    /// Texture object of any target.
+    GlTexture, GenTextures, DeleteTextures
+);
+gl_object!(
+    /// Framebuffer object.
+    GlFramebuffer, GenFramebuffers, DeleteFramebuffers
+);
+gl_object!(
+    /// Sampler object.
+    GlSampler, GenSamplers, DeleteSamplers
+);
+gl_object!(
+    /// Shader program object.
+    GlProgram,
+    create: { gl::CreateProgram() },
+    delete(id): { gl::DeleteProgram(id) }
+);
@@ -0,0 +1,220 @@
+use crate::gl_object::{GlFramebuffer, GlTexture};
+use crate::sampler::SamplerDescriptor;
+
+use std::collections::HashMap;
+use std::ptr;
+
+/// What an ID pass drew at a pixel: the id written by the shader (never 0, which marks the
+/// background) and the window-space depth there, in `0..1`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IdHit {
+    pub id: u32,
+    pub depth: f32,
+}
+
+/// Offscreen target for pixel-precise picking: an `R32UI` colour attachment that shaders write
+/// object ids into (see `resources/shaders/object_id.*`) plus a depth attachment that is read
+/// back alongside them.
+///
+/// Render the pass between `begin` and `end`, then `read` the pixel under the cursor or
+/// `read_rect` a box selection. Coordinates are window pixels from the top-left corner, like
+/// `Camera::screen_to_ray`.
+pub struct IdBuffer {
+    pub fbo: GlFramebuffer,
+    pub id_texture: GlTexture,
+    pub depth_texture: GlTexture,
+    width: u32,
+    height: u32,
+    saved_viewport: [i32; 4],
+}
+
+impl IdBuffer {
+    pub fn new(width: u32, height: u32) -> Result<Self, String> {
+        let mut buffer = IdBuffer {
+            fbo: GlFramebuffer::new(),
+            id_texture: GlTexture::new(),
+            depth_texture: GlTexture::new(),
+            width: 0,
+            height: 0,
+            saved_viewport: [0; 4],
+        };
+        buffer.resize(width, height)?;
+        Ok(buffer)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Reallocates both attachments. Call it on `WindowEvent::Resized`; a 0x0 size (minimised
+    /// window) keeps the current storage.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 || (width == self.width && height == self.height) {
+            return Ok(());
+        }
+        self.width = width;
+        self.height = height;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id_texture.id());
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R32UI as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+            );
+            // integer textures cannot be filtered
+            SamplerDescriptor::nearest().apply_to_texture(gl::TEXTURE_2D);
+
+            gl::BindTexture(gl::TEXTURE_2D, self.depth_texture.id());
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::DEPTH_COMPONENT32F as i32,
+                width as i32,
+                height as i32,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                ptr::null(),
+            );
+            SamplerDescriptor::nearest().apply_to_texture(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo.id());
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.id_texture.id(),
+                0,
+            );
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::TEXTURE_2D,
+                self.depth_texture.id(),
+                0,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("ERRO::ID_BUFFER::INCOMPLETE_FRAMEBUFFER (0x{:x})", status));
+            }
+        }
+        Ok(())
+    }
+
+    /// Binds the buffer for drawing, sets the viewport to cover it and clears ids to 0 and
+    /// depth to the current clear depth. The previous viewport is restored by `end`.
+    pub fn begin(&mut self) {
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, self.saved_viewport.as_mut_ptr());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo.id());
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+
+            let background = [0u32; 4];
+            gl::ClearBufferuiv(gl::COLOR, 0, background.as_ptr());
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    /// Goes back to the default framebuffer and the viewport saved by `begin`.
+    pub fn end(&self) {
+        let [x, y, width, height] = self.saved_viewport;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(x, y, width, height);
+        }
+    }
+
+    /// The id and depth under window pixel `(x, y)`, or `None` on background or outside
+    /// the buffer.
+    pub fn read(&self, x: u32, y: u32) -> Option<IdHit> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let (ids, depths) = self.read_pixels(x, self.height - 1 - y, 1, 1);
+        (ids[0] != 0).then_some(IdHit { id: ids[0], depth: depths[0] })
+    }
+
+    /// Every id drawn inside the rectangle spanned by the two corners (inclusive, in any order),
+    /// each with its nearest depth, for box selection. Sorted by id.
+    ///
+    /// `reverse_z` must match the camera used for the pass, so "nearest" means the right thing.
+    pub fn read_rect(&self, corner_a: (u32, u32), corner_b: (u32, u32), reverse_z: bool) -> Vec<IdHit> {
+        if self.width == 0 || self.height == 0 {
+            return Vec::new();
+        }
+        let x0 = corner_a.0.min(corner_b.0).min(self.width - 1);
+        let x1 = corner_a.0.max(corner_b.0).min(self.width - 1);
+        let y0 = corner_a.1.min(corner_b.1).min(self.height - 1);
+        let y1 = corner_a.1.max(corner_b.1).min(self.height - 1);
+        let (width, height) = (x1 - x0 + 1, y1 - y0 + 1);
+
+        let (ids, depths) = self.read_pixels(x0, self.height - 1 - y1, width, height);
+
+        let mut nearest: HashMap<u32, f32> = HashMap::new();
+        for (&id, &depth) in ids.iter().zip(&depths) {
+            if id == 0 {
+                continue;
+            }
+            nearest
+                .entry(id)
+                .and_modify(|d| *d = if reverse_z { d.max(depth) } else { d.min(depth) })
+                .or_insert(depth);
+        }
+
+        let mut hits: Vec<IdHit> = nearest
+            .into_iter()
+            .map(|(id, depth)| IdHit { id, depth })
+            .collect();
+        hits.sort_by_key(|hit| hit.id);
+        hits
+    }
+
+    /// Reads a block of ids and depths; `(x, y)` is the bottom-left corner in GL convention.
+    /// Stalls until the pass has finished on the GPU.
+    fn read_pixels(&self, x: u32, y: u32, width: u32, height: u32) -> (Vec<u32>, Vec<f32>) {
+        let count = (width * height) as usize;
+        let mut ids = vec![0u32; count];
+        let mut depths = vec![0f32; count];
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo.id());
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                ids.as_mut_ptr() as *mut _,
+            );
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                depths.as_mut_ptr() as *mut _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        (ids, depths)
+    }
+}
@@ -0,0 +1,411 @@
+use crate::vfs;
+
+use glutin::event::{
+    DeviceEvent, ElementState, Event, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// A physical key or mouse button an action can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// Where an axis reads its value from each frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisSource {
+    /// Mouse motion this frame, in pixels.
+    MouseX,
+    MouseY,
+    /// Scroll this frame, in lines.
+    Wheel,
+    /// -1 while the first button is held, +1 while the second is, 0 for both or neither.
+    Buttons(Button, Button),
+}
+
+/// Named actions and axes and the physical inputs bound to them. Several bindings may feed
+/// the same name: an action is held while any of its buttons is, an axis sums its sources.
+///
+/// The text format (see `resources/input/bindings.txt`) has one binding per line:
+///
+/// ```text
+/// action <name> key <VirtualKeyCode> | mouse <Left|Right|Middle|number>
+/// axis <name> mouse-x|mouse-y|wheel [scale]
+/// axis <name> buttons <negative> <positive> [scale]
+/// ```
+///
+/// where a button in an `axis` line is written `key:W` or `mouse:Left`.
+#[derive(Clone, Debug, Default)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Button>>,
+    axes: HashMap<String, Vec<(AxisSource, f32)>>,
+}
+
+impl InputMap {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind_action(&mut self, action: &str, button: Button) {
+        let buttons = self.actions.entry(action.to_string()).or_default();
+        if !buttons.contains(&button) {
+            buttons.push(button);
+        }
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, source: AxisSource, scale: f32) {
+        self.axes.entry(axis.to_string()).or_default().push((source, scale));
+    }
+
+    /// Drops every binding of `name`, action or axis.
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Button] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[(AxisSource, f32)] {
+        self.axes.get(axis).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# input bindings\n");
+
+        let mut actions: Vec<_> = self.actions.iter().collect();
+        actions.sort_by_key(|(name, _)| name.as_str());
+        for (name, buttons) in actions {
+            for button in buttons {
+                let _ = writeln!(text, "action {} {}", name, button_to_text(*button, ' '));
+            }
+        }
+
+        let mut axes: Vec<_> = self.axes.iter().collect();
+        axes.sort_by_key(|(name, _)| name.as_str());
+        for (name, sources) in axes {
+            for (source, scale) in sources {
+                let source = match source {
+                    AxisSource::MouseX => "mouse-x".to_string(),
+                    AxisSource::MouseY => "mouse-y".to_string(),
+                    AxisSource::Wheel => "wheel".to_string(),
+                    AxisSource::Buttons(negative, positive) => format!(
+                        "buttons {} {}",
+                        button_to_text(*negative, ':'),
+                        button_to_text(*positive, ':')
+                    ),
+                };
+                let _ = writeln!(text, "axis {} {} {}", name, source, scale);
+            }
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> anyhow::Result<Self> {
+        let mut map = InputMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let bad = || anyhow::anyhow!("bindings line {}: cannot parse `{}`", number + 1, line);
+            let scale = |word: Option<&&str>| -> anyhow::Result<f32> {
+                word.map_or(Ok(1.0), |w| w.parse().map_err(|_| bad()))
+            };
+
+            match words.as_slice() {
+                ["action", name, kind, value] => {
+                    let button = parse_button(kind, value).ok_or_else(bad)?;
+                    map.bind_action(name, button);
+                }
+                ["axis", name, "buttons", negative, positive, rest @ ..] if rest.len() <= 1 => {
+                    let parse = |word: &str| {
+                        let (kind, value) = word.split_once(':')?;
+                        parse_button(kind, value)
+                    };
+                    let source = AxisSource::Buttons(
+                        parse(negative).ok_or_else(bad)?,
+                        parse(positive).ok_or_else(bad)?,
+                    );
+                    map.bind_axis(name, source, scale(rest.first())?);
+                }
+                ["axis", name, source, rest @ ..] if rest.len() <= 1 => {
+                    let source = match *source {
+                        "mouse-x" => AxisSource::MouseX,
+                        "mouse-y" => AxisSource::MouseY,
+                        "wheel" => AxisSource::Wheel,
+                        _ => return Err(bad()),
+                    };
+                    map.bind_axis(name, source, scale(rest.first())?);
+                }
+                _ => return Err(bad()),
+            }
+        }
+        Ok(map)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        vfs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Self::from_text(&vfs::read_to_string(path)?)
+    }
+}
+
+/// Button state and mouse motion gathered from window events, queried through an `InputMap`.
+///
+/// Feed every event to `handle_event`, read the queries while updating a frame and call
+/// `end_frame` once the frame is done: "pressed" and "released" are true for the one frame in
+/// which the change happened, "held" for as long as the button is down.
+pub struct Input {
+    pub map: InputMap,
+    held: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    mouse_delta: (f32, f32),
+    wheel: f32,
+    cursor_position: (f32, f32),
+}
+
+impl Input {
+
+    pub fn new(map: InputMap) -> Self {
+        Self {
+            map,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            mouse_delta: (0.0, 0.0),
+            wheel: 0.0,
+            cursor_position: (0.0, 0.0),
+        }
+    }
+
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let Some(key) = input.virtual_keycode {
+                        self.set_button(Button::Key(key), input.state == ElementState::Pressed);
+                    }
+                }
+                WindowEvent::MouseInput { state, button, .. } => {
+                    self.set_button(Button::Mouse(*button), *state == ElementState::Pressed);
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    self.wheel += match delta {
+                        MouseScrollDelta::LineDelta(_, y) => *y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                    };
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    self.cursor_position = (position.x as f32, position.y as f32);
+                }
+                // key releases are not delivered to an unfocused window
+                WindowEvent::Focused(false) => {
+                    self.released.extend(self.held.drain());
+                }
+                _ => (),
+            },
+            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
+                self.mouse_delta.0 += delta.0 as f32;
+                self.mouse_delta.1 += delta.1 as f32;
+            }
+            _ => (),
+        }
+    }
+
+    /// Clears the per-frame state: presses, releases, mouse motion and scroll.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.wheel = 0.0;
+    }
+
+    /// Whether any button of `action` went down this frame.
+    pub fn pressed(&self, action: &str) -> bool {
+        self.map.action_bindings(action).iter().any(|b| self.pressed.contains(b))
+    }
+
+    /// Whether a button of `action` went up this frame and none is still down.
+    pub fn released(&self, action: &str) -> bool {
+        !self.held(action) && self.map.action_bindings(action).iter().any(|b| self.released.contains(b))
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.map.action_bindings(action).iter().any(|b| self.held.contains(b))
+    }
+
+    /// Sum of every source bound to `axis`, each times its scale.
+    pub fn axis(&self, axis: &str) -> f32 {
+        self.map
+            .axis_bindings(axis)
+            .iter()
+            .map(|(source, scale)| {
+                let value = match source {
+                    AxisSource::MouseX => self.mouse_delta.0,
+                    AxisSource::MouseY => self.mouse_delta.1,
+                    AxisSource::Wheel => self.wheel,
+                    AxisSource::Buttons(negative, positive) => {
+                        self.held.contains(positive) as i32 as f32 - self.held.contains(negative) as i32 as f32
+                    }
+                };
+                value * scale
+            })
+            .sum()
+    }
+
+    /// Last cursor position in window pixels from the top-left corner.
+    pub fn cursor_position(&self) -> (f32, f32) {
+        self.cursor_position
+    }
+
+    fn set_button(&mut self, button: Button, down: bool) {
+        if down {
+            // key repeat sends more presses while held; only the first one counts
+            if self.held.insert(button) {
+                self.pressed.insert(button);
+            }
+        } else if self.held.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Keys accepted in binding files, by `VirtualKeyCode` variant name.
+        const KEY_NAMES: &[(&str, VirtualKeyCode)] = &[$((stringify!($key), VirtualKeyCode::$key)),*];
+    };
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown, PageUp,
+    Left, Up, Right, Down, Back, Return, Space, Tab,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadDecimal, NumpadEnter,
+    LShift, RShift, LControl, RControl, LAlt, RAlt, LWin, RWin,
+    Apostrophe, Backslash, Comma, Equals, Grave, LBracket, RBracket, Minus, Period, Semicolon, Slash,
+);
+
+fn parse_button(kind: &str, value: &str) -> Option<Button> {
+    match kind {
+        "key" => KEY_NAMES
+            .iter()
+            .find(|(name, _)| *name == value)
+            .map(|(_, key)| Button::Key(*key)),
+        "mouse" => Some(Button::Mouse(match value {
+            "Left" => MouseButton::Left,
+            "Right" => MouseButton::Right,
+            "Middle" => MouseButton::Middle,
+            other => MouseButton::Other(other.parse().ok()?),
+        })),
+        _ => None,
+    }
+}
+
+fn button_to_text(button: Button, separator: char) -> String {
+    match button {
+        Button::Key(key) => format!("key{}{:?}", separator, key),
+        Button::Mouse(MouseButton::Other(n)) => format!("mouse{}{}", separator, n),
+        Button::Mouse(mouse) => format!("mouse{}{:?}", separator, mouse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> InputMap {
+        let mut map = InputMap::new();
+        map.bind_action("jump", Button::Key(VirtualKeyCode::Space));
+        map.bind_action("jump", Button::Mouse(MouseButton::Other(4)));
+        map.bind_action("fire", Button::Mouse(MouseButton::Left));
+        map.bind_axis("look_y", AxisSource::MouseY, -0.5);
+        map.bind_axis("zoom", AxisSource::Wheel, 1.0);
+        map.bind_axis(
+            "strafe",
+            AxisSource::Buttons(Button::Key(VirtualKeyCode::A), Button::Mouse(MouseButton::Right)),
+            2.0,
+        );
+        map
+    }
+
+    fn assert_same_bindings(a: &InputMap, b: &InputMap) {
+        for action in ["jump", "fire"] {
+            assert_eq!(a.action_bindings(action), b.action_bindings(action), "action {}", action);
+        }
+        for axis in ["look_y", "zoom", "strafe"] {
+            assert_eq!(a.axis_bindings(axis), b.axis_bindings(axis), "axis {}", axis);
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let map = map();
+        assert_same_bindings(&InputMap::from_text(&map.to_text()).unwrap(), &map);
+    }
+
+    #[test]
+    fn axis_buttons_take_key_and_mouse() {
+        let map = InputMap::from_text("axis move_x buttons key:A key:D\naxis turn buttons mouse:Left mouse:7").unwrap();
+        assert_eq!(
+            map.axis_bindings("move_x"),
+            [(AxisSource::Buttons(Button::Key(VirtualKeyCode::A), Button::Key(VirtualKeyCode::D)), 1.0)]
+        );
+        assert_eq!(
+            map.axis_bindings("turn"),
+            [(AxisSource::Buttons(Button::Mouse(MouseButton::Left), Button::Mouse(MouseButton::Other(7))), 1.0)]
+        );
+    }
+
+    #[test]
+    fn scales_default_to_one() {
+        let map = InputMap::from_text("axis look mouse-x\naxis look mouse-y -0.25\naxis look wheel 3").unwrap();
+        assert_eq!(
+            map.axis_bindings("look"),
+            [(AxisSource::MouseX, 1.0), (AxisSource::MouseY, -0.25), (AxisSource::Wheel, 3.0)]
+        );
+    }
+
+    #[test]
+    fn mouse_buttons_by_number() {
+        let map = InputMap::from_text("# comment\n\naction back mouse 8\naction pick mouse Middle").unwrap();
+        assert_eq!(map.action_bindings("back"), [Button::Mouse(MouseButton::Other(8))]);
+        assert_eq!(map.action_bindings("pick"), [Button::Mouse(MouseButton::Middle)]);
+        assert!(map.to_text().contains("action back mouse 8\n"));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        for line in [
+            "action jump key NotAKey",
+            "action jump mouse Sideways",
+            "action jump key",
+            "action jump joystick 1",
+            "axis look mouse-z",
+            "axis look mouse-x fast",
+            "axis look mouse-x 1 2",
+            "axis move buttons key:A",
+            "axis move buttons A D",
+            "axis move buttons key:A key:D 1 2",
+            "bind jump key W",
+        ] {
+            let error = InputMap::from_text(&format!("action ok key W\n{}", line)).unwrap_err().to_string();
+            assert!(error.contains("line 2"), "{}: {}", line, error);
+        }
+    }
+}
@@ -1,3 +1,21 @@
+pub mod app;
+pub mod assets;
+pub mod timestep;
 pub mod shader;
+pub mod program_cache;
 pub mod model_loading;
 pub mod camera;
+pub mod color;
+pub mod orbit_controller;
+pub mod fly_controller;
+pub mod camera_path;
+pub mod bounds;
+pub mod gl_object;
+pub mod id_buffer;
+pub mod input;
+pub mod material;
+pub mod sampler;
+pub mod uniform;
+pub mod uniform_buffer;
+pub mod vfs;
+pub mod worker_pool;
@@ -1,373 +1,368 @@
-mod shader;
-use shader::Shader;
-mod material;
-use material::Material;
-mod camera;
-use camera::{Camera, CameraMovement};
+use rust_graphics_engine::app::{self, App, AppConfig, AppContext};
+use rust_graphics_engine::shader::Shader;
+use rust_graphics_engine::program_cache::ProgramCache;
+use rust_graphics_engine::material::Material;
+use rust_graphics_engine::color::srgb_to_linear;
+use rust_graphics_engine::camera::{Camera, CameraMode};
+use rust_graphics_engine::camera_path::{CameraPath, Keyframe};
+use rust_graphics_engine::fly_controller::FlyController;
+use rust_graphics_engine::uniform_buffer::{CameraBlock, LightBlock, UniformBuffer};
+use rust_graphics_engine::gl_object::{GlBuffer, GlVertexArray};
 use cgmath::Point3;
 
-use glutin::event::{Event, WindowEvent};
-use glutin::event_loop::{ControlFlow, EventLoop};
-use glutin::{Api, ContextBuilder, GlRequest};
-
-use glutin::window::{WindowBuilder, CursorGrabMode};
 use std::ptr;
 
-use cgmath::{Matrix3, Matrix4, Vector3, Deg, SquareMatrix, Matrix, perspective};
-use std::time::Instant;
-use glutin::event::VirtualKeyCode;
+use cgmath::{Matrix3, Matrix4, Vector3, Deg, SquareMatrix, Matrix};
+
+struct Cubes {
+    lighting_shader: Shader,
+    light_cube_shader: Shader,
+    camera_ubo: UniformBuffer<CameraBlock>,
+    light_ubo: UniformBuffer<LightBlock>,
+
+    camera: Camera,
+    /// Pose at the previous tick, blended with `camera` when rendering.
+    previous_camera: Camera,
+    fly: FlyController,
+    camera_path: CameraPath,
+    playback_start: Option<f32>,
+    mode_before_playback: CameraMode,
+
+    emerald: Material,
+    gold: Material,
+
+    // liberados no drop, junto com o resto; o VBO só precisa viver enquanto os VAOs o usam
+    _vbo: GlBuffer,
+    cube_vao: GlVertexArray,
+    light_cube_vao: GlVertexArray,
+}
 
-use glutin::event::{DeviceEvent}; // Adicione DeviceEvent e ElementState
+impl App for Cubes {
 
-fn main() {
-    
-    let start_time = Instant::now();
+    fn init(_ctx: &mut AppContext) -> anyhow::Result<Self> {
+        // obs: r#" "# é uma raw string literal. Não é necessário \n ou \". A string aparece exatamente
+        // como está entre aspas
 
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().with_title("LWA-Graphics-Engine");
+        let program_cache = ProgramCache::new("target/shader_cache");
 
-    let gl_context = ContextBuilder::new()
-        .with_gl(GlRequest::Specific(Api::OpenGl, (3,3)))
-        .build_windowed(window, &event_loop)
-        .expect("Cannot create windowed context");
+        let lighting_shader = Shader::new_cached("resources/shaders/basic_lighting.vs", "resources/shaders/basic_lighting.fs", &program_cache)
+            .map_err(anyhow::Error::msg)?;
 
-    let gl_context = unsafe {
-        gl_context
-            .make_current()
-            .expect("Failed to make context current")
-    };
+        let light_cube_shader = Shader::new_cached("resources/shaders/light_cube.vs", "resources/shaders/light_cube.fs", &program_cache)
+            .map_err(anyhow::Error::msg)?;
 
-    gl::load_with(|ptr| gl_context.get_proc_address(ptr) as *const _);
+        // UBOs compartilhados: atualizados uma vez por frame e lidos por todos os shaders
+        let camera_ubo = UniformBuffer::<CameraBlock>::new();
+        let light_ubo = UniformBuffer::<LightBlock>::new();
 
+        lighting_shader.bind_uniform_block::<CameraBlock>().map_err(anyhow::Error::msg)?;
+        lighting_shader.bind_uniform_block::<LightBlock>().map_err(anyhow::Error::msg)?;
+        light_cube_shader.bind_uniform_block::<CameraBlock>().map_err(anyhow::Error::msg)?;
 
-    unsafe {
-        gl::Enable(gl::DEPTH_TEST);
-    }
-    // obs: r#" "# é uma raw string literal. Não é necessário \n ou \". A string aparece exatamente
-    // como está entre aspas
-
-    let lighting_shader = Shader::new("resources/shaders/basic_lighting.vs", "resources/shaders/basic_lighting.fs")
-        .expect("Failed to create lighting shader");
-
-    let light_cube_shader = Shader::new("resources/shaders/light_cube.vs", "resources/shaders/light_cube.fs")
-        .expect("Failed to create light cube shader");
-
-    let mut camera = Camera::new(
-
-        Point3::new(0.0, 0.0, 3.0),
-        -90.0,
-        0.0
-    );
-
-    let mut w_pressed = false;
-    let mut s_pressed = false;
-    let mut a_pressed = false;
-    let mut d_pressed = false;
-
-
-// Criando um material de "Esmeralda" (exemplo)
-    let emerald = Material::new(
-        Vector3::new(0.0215, 0.1745, 0.0215),
-        Vector3::new(0.07568, 0.61424, 0.07568),
-        Vector3::new(0.633, 0.727811, 0.633),
-        0.6 * 128.0
-    );
-
-    let gold = Material::new(
-            Vector3::new(0.24725, 0.1995, 0.0745),
-            Vector3::new(0.75164, 0.60648, 0.22648),
-            Vector3::new(0.62828, 0.55580, 0.36606),
-            51.0
-    );
-
-
-//Vertices e normais
-let vertices: [f32; 216] = [
-        -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
-         0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
-         0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
-         0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
-        -0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
-        -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
-
-        -0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
-         0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
-         0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
-         0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
-        -0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
-        -0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
-
-        -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
-        -0.5,  0.5, -0.5, -1.0,  0.0,  0.0,
-        -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
-        -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
-        -0.5, -0.5,  0.5, -1.0,  0.0,  0.0,
-        -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
-
-         0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
-         0.5,  0.5, -0.5,  1.0,  0.0,  0.0,
-         0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
-         0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
-         0.5, -0.5,  0.5,  1.0,  0.0,  0.0,
-         0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
-
-        -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
-         0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
-         0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
-         0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
-        -0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
-        -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
-
-        -0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
-         0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
-         0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
-         0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
-        -0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
-        -0.5,  0.5, -0.5,  0.0,  1.0,  0.0
-    ];
-
-     //Inicia duas variáveis mutáveis e elas vão ser reescritas por funções do opengl, então não importa o valor inicial.
-    let mut vbo: u32 = 0;
-    let mut cube_vao: u32 = 0;
-    let mut light_cube_vao: u32 = 0;
-
-    unsafe {
-
-        gl::GenBuffers(1, &mut vbo); //Cria 1 unidade de buffer e atribui um id à vbo para o buffer gerado
-
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo); //A partir deste ponto, qualquer chamada de buffer
-        //vai ser usada para configurar o atual bound buffer.
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
-            vertices.as_ptr() as *const _,
-            gl::STATIC_DRAW
+        let camera = Camera::new(
+
+            Point3::new(0.0, 0.0, 3.0),
+            -90.0,
+            0.0
         );
 
-        //Cubo principal
-        gl::GenVertexArrays(1, &mut cube_vao);
-        gl::BindVertexArray(cube_vao);
-
-        let stride =(6 * std::mem::size_of::<f32>()) as gl::types::GLint;//strinde
-        gl::VertexAttribPointer( //Em relação ao current bounded buffer
-            0, //layout (location = 0)
-            3, // size (vec3)
-            gl::FLOAT,
-            gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
-            stride,
-            ptr::null(), //offset (posição os os dados começam no buffer)
+    // Criando um material de "Esmeralda" (exemplo); valores da tabela clássica, em sRGB
+        let emerald = Material::from_srgb(
+            Vector3::new(0.0215, 0.1745, 0.0215),
+            Vector3::new(0.07568, 0.61424, 0.07568),
+            Vector3::new(0.633, 0.727811, 0.633),
+            0.6 * 128.0
         );
-        gl::EnableVertexAttribArray(0);
-
-        let offset = (3 * std::mem::size_of::<f32>()) as *const _ ;
-        gl::VertexAttribPointer( //Em relação ao current bounded buffer
-            1, //layout (location = 0)
-            3, // size (vec3)
-            gl::FLOAT,
-            gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
-            stride,
-            offset, //offset (posição os os dados começam no buffer)
+
+        let gold = Material::from_srgb(
+                Vector3::new(0.24725, 0.1995, 0.0745),
+                Vector3::new(0.75164, 0.60648, 0.22648),
+                Vector3::new(0.62828, 0.55580, 0.36606),
+                51.0
         );
-        gl::EnableVertexAttribArray(1);
 
 
-        //Cubo de luz
-        gl::GenVertexArrays(1, &mut light_cube_vao);
-        gl::BindVertexArray(light_cube_vao);
+    //Vertices e normais
+    let vertices: [f32; 216] = [
+            -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+             0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+             0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+             0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+            -0.5,  0.5, -0.5,  0.0,  0.0, -1.0,
+            -0.5, -0.5, -0.5,  0.0,  0.0, -1.0,
+
+            -0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
+             0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
+             0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
+             0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
+            -0.5,  0.5,  0.5,  0.0,  0.0,  1.0,
+            -0.5, -0.5,  0.5,  0.0,  0.0,  1.0,
+
+            -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
+            -0.5,  0.5, -0.5, -1.0,  0.0,  0.0,
+            -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
+            -0.5, -0.5, -0.5, -1.0,  0.0,  0.0,
+            -0.5, -0.5,  0.5, -1.0,  0.0,  0.0,
+            -0.5,  0.5,  0.5, -1.0,  0.0,  0.0,
+
+             0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
+             0.5,  0.5, -0.5,  1.0,  0.0,  0.0,
+             0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
+             0.5, -0.5, -0.5,  1.0,  0.0,  0.0,
+             0.5, -0.5,  0.5,  1.0,  0.0,  0.0,
+             0.5,  0.5,  0.5,  1.0,  0.0,  0.0,
+
+            -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+             0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+             0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+             0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+            -0.5, -0.5,  0.5,  0.0, -1.0,  0.0,
+            -0.5, -0.5, -0.5,  0.0, -1.0,  0.0,
+
+            -0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
+             0.5,  0.5, -0.5,  0.0,  1.0,  0.0,
+             0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+             0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+            -0.5,  0.5,  0.5,  0.0,  1.0,  0.0,
+            -0.5,  0.5, -0.5,  0.0,  1.0,  0.0
+        ];
+
+        let vbo = GlBuffer::new(); //Cria 1 unidade de buffer e atribui um id à vbo para o buffer gerado
+        let cube_vao = GlVertexArray::new();
+        let light_cube_vao = GlVertexArray::new();
+
+        unsafe {
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id()); //A partir deste ponto, qualquer chamada de buffer
+            //vai ser usada para configurar o atual bound buffer.
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (vertices.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::STATIC_DRAW
+            );
+
+            //Cubo principal
+            gl::BindVertexArray(cube_vao.id());
+
+            let stride =(6 * std::mem::size_of::<f32>()) as gl::types::GLint;//strinde
+            gl::VertexAttribPointer( //Em relação ao current bounded buffer
+                0, //layout (location = 0)
+                3, // size (vec3)
+                gl::FLOAT,
+                gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
+                stride,
+                ptr::null(), //offset (posição os os dados começam no buffer)
+            );
+            gl::EnableVertexAttribArray(0);
+
+            let offset = (3 * std::mem::size_of::<f32>()) as *const _ ;
+            gl::VertexAttribPointer( //Em relação ao current bounded buffer
+                1, //layout (location = 0)
+                3, // size (vec3)
+                gl::FLOAT,
+                gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
+                stride,
+                offset, //offset (posição os os dados começam no buffer)
+            );
+            gl::EnableVertexAttribArray(1);
+
+
+            //Cubo de luz
+            gl::BindVertexArray(light_cube_vao.id());
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.id());
+            //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE)
+
+            gl::VertexAttribPointer( //Em relação ao current bounded buffer
+                0, //layout (location = 0)
+                3, // size (vec3)
+                gl::FLOAT,
+                gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
+                stride,
+                ptr::null(), //offset (posição os os dados começam no buffer)
+            );
+            gl::EnableVertexAttribArray(0);
 
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        //gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE)
+        }
 
-        gl::VertexAttribPointer( //Em relação ao current bounded buffer
-            0, //layout (location = 0)
-            3, // size (vec3)
-            gl::FLOAT,
-            gl::FALSE, //Os dados já estão normalizados, então False para a normalizalção
-            stride,
-            ptr::null(), //offset (posição os os dados começam no buffer)
-        );
-        gl::EnableVertexAttribArray(0);
+        Ok(Cubes {
+            lighting_shader,
+            light_cube_shader,
+            camera_ubo,
+            light_ubo,
+            previous_camera: camera.clone(),
+            camera,
+            fly: FlyController::new(),
+            camera_path: CameraPath::new(),
+            playback_start: None,
+            mode_before_playback: CameraMode::Fps,
+            emerald,
+            gold,
+            _vbo: vbo,
+            cube_vao,
+            light_cube_vao,
+        })
+    }
 
+    fn on_resize(&mut self, _ctx: &mut AppContext, width: u32, height: u32) {
+        self.camera.on_resize(width, height);
+        self.previous_camera.on_resize(width, height);
     }
 
-    let mut last_frame_time = 0.0;
-
-    event_loop.run(move |event, _ , control_flow| { //??????
-
-        *control_flow = ControlFlow::Poll;
-
-        match event {
-            Event::LoopDestroyed => {
-
-                unsafe {
-                    gl::DeleteVertexArrays(1, &cube_vao);
-                    gl::DeleteVertexArrays(1, &light_cube_vao);
-                    gl::DeleteBuffers(1, &vbo);
-                }
-                return;
-            },
-            Event::WindowEvent { event, .. } => match event {
-            WindowEvent::Resized(physical_size) => {
-                gl_context.resize(physical_size);
-                unsafe {
-                    gl::Viewport(
-                        0,
-                        0,
-                        physical_size.width as i32,
-                        physical_size.height as i32,
-                    );
-                }
-            }
-        
-            WindowEvent::Focused(true) => {
-                let window = gl_context.window();
-            
-                if window.set_cursor_grab(CursorGrabMode::Locked).is_err() {
-                    let _ = window.set_cursor_grab(CursorGrabMode::Confined);
-                }
-            
-                window.set_cursor_visible(false);
-            }
-        
-            WindowEvent::Focused(false) => {
-                let window = gl_context.window();
-                let _ = window.set_cursor_grab(CursorGrabMode::None);
-                window.set_cursor_visible(true);
-            }
-        
-            WindowEvent::CloseRequested => {
-                *control_flow = ControlFlow::Exit;
+    // teclas e mouse vêm de resources/input/bindings.txt; WASD move, Space/Ctrl sobem e descem,
+    // V alterna entre a câmera FPS e o voo livre (com roll em Q/E).
+    // K grava a pose atual como keyframe (2s entre keys), P toca/para o percurso,
+    // F5 salva e F9 carrega de camera_path.txt
+    fn update(&mut self, ctx: &mut AppContext, delta_time: f32) {
+        let input = &ctx.input;
+        let current_time = ctx.time();
+        self.previous_camera = self.camera.clone();
+
+        if input.pressed("toggle_camera_mode") {
+            let mode = match self.camera.mode() {
+                CameraMode::Fps => CameraMode::Free,
+                CameraMode::Free => CameraMode::Fps,
+            };
+            self.camera.set_mode(mode);
+        }
+        if input.pressed("record_keyframe") {
+            let time = self.camera_path.keyframes().len() as f32 * 2.0;
+            self.camera_path.add_keyframe(Keyframe::from_camera(time, &self.camera));
+        }
+        if input.pressed("play_path") {
+            if self.playback_start.is_some() {
+                self.playback_start = None;
+                self.camera.set_mode(self.mode_before_playback);
+            } else if self.camera_path.keyframes().len() >= 2 {
+                self.playback_start = Some(current_time);
+                self.mode_before_playback = self.camera.mode();
             }
-        
-            WindowEvent::KeyboardInput { input, .. } => {
-                if let Some(keycode) = input.virtual_keycode {
-                    let is_pressed = input.state == glutin::event::ElementState::Pressed;
-                
-                    match keycode {
-                        VirtualKeyCode::W => w_pressed = is_pressed,
-                        VirtualKeyCode::S => s_pressed = is_pressed,
-                        VirtualKeyCode::A => a_pressed = is_pressed,
-                        VirtualKeyCode::D => d_pressed = is_pressed,
-                        VirtualKeyCode::Escape if is_pressed => {
-                            *control_flow = ControlFlow::Exit;
-                        }
-                        _ => (),
-                    }
-                }
+        }
+        if input.pressed("save_path")
+            && let Err(e) = self.camera_path.save("camera_path.txt")
+        {
+            eprintln!("Failed to save camera path: {}", e);
+        }
+        if input.pressed("load_path") {
+            match CameraPath::load("camera_path.txt") {
+                Ok(path) => self.camera_path = path,
+                Err(e) => eprintln!("Failed to load camera path: {}", e),
             }
-        
-            _ => (),
-            }       
-
-            Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } => {
-                camera.process_mouse(delta.0 as f32, -delta.1 as f32); 
-            },
-        
-            Event::RedrawRequested(_) => {
-
+        }
 
-                let current_time = start_time.elapsed().as_secs_f32();
-                let delta_time = current_time - last_frame_time;
-                last_frame_time = current_time;
+        self.fly.update(input, &mut self.camera, delta_time);
 
-                if w_pressed {camera.process_keyboard(CameraMovement::Forward, delta_time);}
-                if s_pressed {camera.process_keyboard(CameraMovement::Backward, delta_time);}
-                if a_pressed {camera.process_keyboard(CameraMovement::Left, delta_time);}
-                if d_pressed {camera.process_keyboard(CameraMovement::Right, delta_time);}
+        if let Some(start) = self.playback_start {
+            let time = current_time - start;
+            self.camera_path.apply(&mut self.camera, time);
+            if !self.camera_path.looping && time > self.camera_path.duration() {
+                self.playback_start = None;
+                self.camera.set_mode(self.mode_before_playback);
+            }
+        }
 
-                let time_value = start_time.elapsed().as_secs_f32();
+        if ctx.input.pressed("quit") {
+            ctx.exit();
+        }
+    }
 
-                unsafe {
-                    gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+    fn render(&mut self, ctx: &mut AppContext, alpha: f32) {
+        // o estado desenhado fica entre o tick anterior e o atual, então a animação também
+        let time_value = ctx.time() - (1.0 - alpha) * ctx.timestep().dt();
+        let camera = self.previous_camera.interpolated(&self.camera, alpha);
 
-                    let light_x = 3.5 * (time_value * 1.0).sin();
-                    let light_y = 3.5 * (time_value * 1.0).cos();
-                    
-                    let light_pos = Vector3::new(light_x, 1.0, light_y);
-                    
-                    let light_color = Vector3::new(1.0, 1.0, 1.0);
-                    let diffuse_color = light_color * 0.5;
-                    let ambient_color = diffuse_color * 0.3;
-                    
+        // o framebuffer é sRGB: a cor de limpeza também é linear
+        let background = srgb_to_linear(0.1);
+        unsafe {
+            gl::ClearColor(background, background, background, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-                    //desenha o cubo principal
-                    lighting_shader.use_program();
-                    lighting_shader.set_vec3("light.position", &light_pos);
-                    lighting_shader.set_vec3("light.diffuse", &diffuse_color);
-                    lighting_shader.set_vec3("light.ambient", &ambient_color);
-                    lighting_shader.set_vec3("light.specular", &Vector3::new(1.0, 1.0, 1.0));
+            let light_x = 3.5 * (time_value * 1.0).sin();
+            let light_y = 3.5 * (time_value * 1.0).cos();
+            
+            let light_pos = Vector3::new(light_x, 1.0, light_y);
+            
+            let light_color = Vector3::new(1.0, 1.0, 1.0);
+            let diffuse_color = light_color * 0.5;
+            let ambient_color = diffuse_color * 0.3;
+            
 
-                    let cam_pos = Vector3::new(camera.position.x, camera.position.y, camera.position.z); 
-                    lighting_shader.set_vec3("viewPos", &cam_pos);
+            self.light_ubo.update(&LightBlock::new(
+                light_pos,
+                ambient_color,
+                diffuse_color,
+                Vector3::new(1.0, 1.0, 1.0),
+            ));
 
-                    
-                    let view = camera.get_view_matrix();
-                    let projection = perspective(Deg(45.0), 800.0 / 600.0, 0.1, 100.0);
+            let view = camera.get_view_matrix();
+            let projection = camera.get_projection_matrix();
 
-                    lighting_shader.set_mat4("view", &view);
-                    lighting_shader.set_mat4("projection", &projection);
+            self.camera_ubo.update(&CameraBlock::new(&view, &projection, camera.position));
 
+            //desenha o cubo principal
+            self.lighting_shader.use_program();
 
-                    emerald.apply(&lighting_shader, "material");                  
-                    let mut model = Matrix4::from_translation(Vector3::new(1.0, 0.0, 0.0));
-                    model = model * Matrix4::from_angle_y(Deg(-time_value * 15.0));
-                    model = model * Matrix4::from_angle_x(Deg(-time_value * 13.0));
+            self.emerald.apply(&self.lighting_shader, "material");                  
+            let mut model = Matrix4::from_translation(Vector3::new(1.0, 0.0, 0.0));
+            model = model * Matrix4::from_angle_y(Deg(-time_value * 15.0));
+            model = model * Matrix4::from_angle_x(Deg(-time_value * 13.0));
 
-                    lighting_shader.set_mat4("model", &model);
+            self.lighting_shader.set_mat4("model", &model);
 
-                    let normal_matrix = Matrix3::from_cols(
-                        model.x.truncate(),
-                        model.y.truncate(),
-                        model.z.truncate()
-                    ).invert().unwrap().transpose();
+            let normal_matrix = Matrix3::from_cols(
+                model.x.truncate(),
+                model.y.truncate(),
+                model.z.truncate()
+            ).invert().unwrap().transpose();
 
-                    lighting_shader.set_mat3("normalMatrix", &normal_matrix);
+            self.lighting_shader.set_mat3("normalMatrix", &normal_matrix);
 
-                    gl::BindVertexArray(cube_vao);
-                    gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::BindVertexArray(self.cube_vao.id());
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
-                    gold.apply(&lighting_shader, "material");
-                    let mut model = Matrix4::from_translation(Vector3::new(-1.0, 0.0, 0.0));
-                    model = model * Matrix4::from_angle_y(Deg(time_value * 10.0));
-                    model = model * Matrix4::from_angle_x(Deg(time_value * 16.0));
+            self.gold.apply(&self.lighting_shader, "material");
+            let mut model = Matrix4::from_translation(Vector3::new(-1.0, 0.0, 0.0));
+            model = model * Matrix4::from_angle_y(Deg(time_value * 10.0));
+            model = model * Matrix4::from_angle_x(Deg(time_value * 16.0));
 
-                    lighting_shader.set_mat4("model", &model);
+            self.lighting_shader.set_mat4("model", &model);
 
-                    let normal_matrix = Matrix3::from_cols(
-                        model.x.truncate(),
-                        model.y.truncate(),
-                        model.z.truncate()
-                    ).invert().unwrap().transpose();
+            let normal_matrix = Matrix3::from_cols(
+                model.x.truncate(),
+                model.y.truncate(),
+                model.z.truncate()
+            ).invert().unwrap().transpose();
 
-                    lighting_shader.set_mat3("normalMatrix", &normal_matrix);
+            self.lighting_shader.set_mat3("normalMatrix", &normal_matrix);
 
-                    gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
 
-                    //desenha o cubo lampada
-                    light_cube_shader.use_program();
-                    light_cube_shader.set_mat4("projection", &projection);
-                    light_cube_shader.set_mat4("view", &view);
+            //desenha o cubo lampada
+            self.light_cube_shader.use_program();
 
 
-                    //aplica uma transformação que primeiro move a lampada do centro e demois
-                    //reescala
-                    let mut model = Matrix4::from_translation(light_pos);
-                    model = model * Matrix4::from_scale(0.2);
+            //aplica uma transformação que primeiro move a lampada do centro e demois
+            //reescala
+            let mut model = Matrix4::from_translation(light_pos);
+            model = model * Matrix4::from_scale(0.2);
 
-                    light_cube_shader.set_mat4("model", &model);
+            self.light_cube_shader.set_mat4("model", &model);
 
-                    gl::BindVertexArray(light_cube_vao);
-                    gl::DrawArrays(gl::TRIANGLES, 0, 36)
+            gl::BindVertexArray(self.light_cube_vao.id());
+            gl::DrawArrays(gl::TRIANGLES, 0, 36)
 
-                }
-                gl_context.swap_buffers().unwrap();
-            }
-            Event::MainEventsCleared => {
-                gl_context.window().request_redraw();
-            }
-            _ => (),
         }
-    });
+    }
+}
+
+fn main() {
+    // com resources.pak (cargo run --bin pack) os recursos vêm do arquivo, montado antes das
+    // teclas; sem ele, de resources/
+    app::run::<Cubes>(AppConfig {
+        archive: Some("resources.pak".to_string()),
+        bindings: Some("resources/input/bindings.txt".to_string()),
+        ..AppConfig::default()
+    }.with_args());
 }
@@ -1,15 +1,40 @@
 use cgmath::Vector3;
+use crate::color::srgb_to_linear3;
+use crate::model_loading::texture::Texture;
+use crate::sampler::Sampler;
 use crate::shader::Shader;
+use crate::uniform::Uniform;
 
+use std::rc::Rc;
+
+/// A sampler uniform of the material shader and the texture bound to it. `sampler`, when
+/// set, overrides the texture's own sampling state.
+pub struct TextureSlot {
+    pub name: String,
+    pub texture: Rc<Texture>,
+    pub sampler: Option<Rc<Sampler>>,
+}
+
+/// Colour terms are linear, as the shaders light in linear space and the framebuffer encodes
+/// to sRGB on write. Values picked by eye or read from MTL files are sRGB: use `from_srgb`.
 pub struct Material {
     pub ambient: Vector3<f32>,
     pub diffuse: Vector3<f32>,
     pub specular: Vector3<f32>,
     pub shininess: f32,
+
+    pub textures: Vec<TextureSlot>,
+    pub params: Vec<(String, Box<dyn Uniform>)>,
 }
 
 impl Material {
 
+    pub const DIFFUSE_MAP: &'static str = "texture_diffuse1";
+    pub const SPECULAR_MAP: &'static str = "texture_specular1";
+    pub const NORMAL_MAP: &'static str = "texture_normal1";
+    /// Every slot model loading fills from the maps of a material file.
+    pub const MAPS: [&'static str; 3] = [Self::DIFFUSE_MAP, Self::SPECULAR_MAP, Self::NORMAL_MAP];
+
     pub fn new(
         ambient: Vector3<f32>,
         diffuse: Vector3<f32>,
@@ -21,13 +46,96 @@ impl Material {
             diffuse,
             specular,
             shininess,
+            textures: Vec::new(),
+            params: Vec::new(),
         }
     }
 
+    /// Like `new`, with the colours given in sRGB and converted to linear.
+    pub fn from_srgb(
+        ambient: Vector3<f32>,
+        diffuse: Vector3<f32>,
+        specular: Vector3<f32>,
+        shininess: f32
+    ) -> Self {
+        Self::new(
+            srgb_to_linear3(ambient),
+            srgb_to_linear3(diffuse),
+            srgb_to_linear3(specular),
+            shininess,
+        )
+    }
+
+    /// Binds `texture` to the sampler uniform `name`, replacing whatever was there.
+    pub fn with_texture(mut self, name: &str, texture: Rc<Texture>) -> Self {
+        self.textures.retain(|slot| slot.name != name);
+        self.textures.push(TextureSlot { name: name.to_string(), texture, sampler: None });
+        self
+    }
+
+    /// Samples the texture in slot `name` through `sampler`, which can be shared with other
+    /// slots and materials. Does nothing if the slot has no texture.
+    pub fn with_sampler(mut self, name: &str, sampler: Rc<Sampler>) -> Self {
+        if let Some(slot) = self.textures.iter_mut().find(|slot| slot.name == name) {
+            slot.sampler = Some(sampler);
+        }
+        self
+    }
+
+    /// Extra uniform set by `bind`, for shader inputs beyond the four colour terms.
+    pub fn with_param(mut self, name: &str, value: impl Uniform + 'static) -> Self {
+        self.params.retain(|(param, _)| param != name);
+        self.params.push((name.to_string(), Box::new(value)));
+        self
+    }
+
+    /// `#define`s the shader must be compiled with to read this material: one `HAS_<SLOT>`
+    /// per texture slot, e.g. `HAS_TEXTURE_DIFFUSE1`.
+    pub fn defines(&self) -> Vec<String> {
+        let mut defines: Vec<String> = self
+            .textures
+            .iter()
+            .map(|slot| Self::define(&slot.name))
+            .collect();
+        defines.sort();
+        defines
+    }
+
+    /// The `#define` that tells the shader texture slot `slot` is bound.
+    pub fn define(slot: &str) -> String {
+        format!("HAS_{}", slot.to_uppercase())
+    }
+
     pub fn apply(&self, shader: &Shader, uniform_name: &str) {
-        shader.set_vec3(&format!("{}.ambient", uniform_name), &self.ambient);
-        shader.set_vec3(&format!("{}.diffuse", uniform_name), &self.diffuse);
-        shader.set_vec3(&format!("{}.specular", uniform_name), &self.specular);
-        shader.set_float(&format!("{}.shininess", uniform_name), self.shininess);
+        shader.set(&format!("{}.ambient", uniform_name), self.ambient);
+        shader.set(&format!("{}.diffuse", uniform_name), self.diffuse);
+        shader.set(&format!("{}.specular", uniform_name), self.specular);
+        shader.set(&format!("{}.shininess", uniform_name), self.shininess);
+    }
+
+    /// Sets everything the material owns on `shader`, which must be in use: the `material`
+    /// struct, every texture slot on its own unit (in slot order, from unit 0) and the params.
+    pub fn bind(&self, shader: &Shader) {
+        self.apply(shader, "material");
+
+        for (unit, slot) in self.textures.iter().enumerate() {
+            let bound = slot.texture.at_unit(unit as u32).with_sampler(slot.sampler.as_deref());
+            shader.set(&slot.name, bound);
+        }
+
+        for (name, value) in &self.params {
+            shader.set(name, value.as_ref());
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new(
+            Vector3::new(0.2, 0.2, 0.2),
+            Vector3::new(0.8, 0.8, 0.8),
+            Vector3::new(0.5, 0.5, 0.5),
+            32.0,
+        )
     }
 }
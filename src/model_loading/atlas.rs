@@ -0,0 +1,292 @@
+use super::texture::{Texture, TextureDescriptor};
+use crate::vfs;
+
+use anyhow::Context;
+use glam::Vec2;
+use image::{DynamicImage, RgbaImage};
+use std::collections::HashMap;
+
+/// Where one source image ended up inside an atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// Top-left corner and size in atlas pixels, top row first like the source images.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// `uv * uv_scale + uv_offset` maps texture coordinates of the source image into the
+    /// atlas, in the same bottom-left convention as `Texture::from_image`.
+    pub uv_offset: Vec2,
+    pub uv_scale: Vec2,
+}
+
+impl AtlasRegion {
+    /// Maps a `0..1` coordinate of the source image into the atlas. Coordinates outside `0..1`
+    /// land in neighbouring regions: repeating textures cannot be atlased this way.
+    pub fn remap(&self, uv: Vec2) -> Vec2 {
+        uv * self.uv_scale + self.uv_offset
+    }
+}
+
+/// Many images packed into one texture, each reachable by the key it was added with.
+pub struct Atlas {
+    pub texture: Texture,
+    pub regions: HashMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn region(&self, key: &str) -> Option<&AtlasRegion> {
+        self.regions.get(key)
+    }
+}
+
+/// Collects images and packs them into a single RGBA texture with shelf packing.
+///
+/// Each image gets `padding` pixels of its own edge texels around it, so linear filtering at
+/// a region's border does not pick up the neighbour. Mipmapped atlases still bleed at the
+/// smaller levels once a level's texels span more than the padding.
+pub struct AtlasBuilder {
+    max_size: u32,
+    padding: u32,
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    /// `max_size` bounds both sides of the atlas in pixels.
+    pub fn new(max_size: u32) -> Self {
+        Self { max_size, padding: 2, images: Vec::new() }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Adds `image` under `key`, replacing an earlier image with the same key.
+    pub fn add(&mut self, key: &str, image: &DynamicImage) {
+        self.images.retain(|(k, _)| k != key);
+        self.images.push((key.to_string(), image.to_rgba8()));
+    }
+
+    /// Adds the image at `path`, keyed by the path.
+    pub fn add_file(&mut self, path: &str) -> anyhow::Result<()> {
+        let image = vfs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(image::load_from_memory(&bytes)?))
+            .with_context(|| format!("Failed to load atlas image {}", path))?;
+        self.add(path, &image);
+        Ok(())
+    }
+
+    /// Packs the images and uploads the atlas. Fails if they do not fit in `max_size`.
+    pub fn build(&self, descriptor: &TextureDescriptor) -> anyhow::Result<Atlas> {
+        let (canvas, regions) = self.compose()?;
+        let texture = Texture::from_image(&DynamicImage::ImageRgba8(canvas), descriptor)?;
+        Ok(Atlas { texture, regions })
+    }
+
+    /// Packs the images into the atlas image, without touching GL.
+    fn compose(&self) -> anyhow::Result<(RgbaImage, HashMap<String, AtlasRegion>)> {
+        if let Some((key, _)) = self.images.iter().find(|(_, image)| image.width() == 0 || image.height() == 0) {
+            anyhow::bail!("atlas image {} has no pixels", key);
+        }
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, image)| (image.width() + 2 * self.padding, image.height() + 2 * self.padding))
+            .collect();
+        let Packing { width, height, positions } = self.pack(&sizes)?;
+
+        let mut canvas = RgbaImage::new(width, height);
+        let mut regions = HashMap::new();
+        for ((key, image), (x, y)) in self.images.iter().zip(positions) {
+            blit_extruded(&mut canvas, image, x, y, self.padding);
+
+            let (x, y) = (x + self.padding, y + self.padding);
+            let (w, h) = (image.width(), image.height());
+            let atlas_size = Vec2::new(width as f32, height as f32);
+            regions.insert(
+                key.clone(),
+                AtlasRegion {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                    // the atlas is flipped on upload, so the region's bottom row is at v = 0
+                    uv_offset: Vec2::new(x as f32, (height - y - h) as f32) / atlas_size,
+                    uv_scale: Vec2::new(w as f32, h as f32) / atlas_size,
+                },
+            );
+        }
+        Ok((canvas, regions))
+    }
+
+    /// Finds the smallest power-of-two atlas, growing width then height in turn, that the
+    /// shelf packer fits `sizes` into. Returns its size and the top-left corner of each item.
+    fn pack(&self, sizes: &[(u32, u32)]) -> anyhow::Result<Packing> {
+        let area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+        let widest = sizes.iter().map(|s| s.0).max().unwrap_or(1);
+        let tallest = sizes.iter().map(|s| s.1).max().unwrap_or(1);
+
+        let too_large = || {
+            anyhow::anyhow!(
+                "{} images ({} pixels with padding) do not fit in a {}x{} atlas",
+                sizes.len(), area, self.max_size, self.max_size
+            )
+        };
+
+        // sizes past u32 cannot fit any max_size either
+        let mut width = widest
+            .max((area as f64).sqrt() as u32)
+            .max(1)
+            .checked_next_power_of_two()
+            .ok_or_else(too_large)?;
+        let mut height = tallest.max(1).checked_next_power_of_two().ok_or_else(too_large)?;
+        loop {
+            if width > self.max_size || height > self.max_size {
+                return Err(too_large());
+            }
+            if let Some(positions) = shelf_pack(sizes, width, height) {
+                return Ok(Packing { width, height, positions });
+            }
+            let grown = if height < width {
+                height.checked_mul(2).map(|height| (width, height))
+            } else {
+                width.checked_mul(2).map(|width| (width, height))
+            };
+            (width, height) = grown.ok_or_else(too_large)?;
+        }
+    }
+}
+
+struct Packing {
+    width: u32,
+    height: u32,
+    positions: Vec<(u32, u32)>,
+}
+
+/// Places items on shelves filled left to right, tallest items first so shelves waste little
+/// height. `None` if they do not fit in `width` x `height`.
+fn shelf_pack(sizes: &[(u32, u32)], width: u32, height: u32) -> Option<Vec<(u32, u32)>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((sizes[i].1, sizes[i].0)));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if w > width {
+            return None;
+        }
+        // x <= width and y <= height hold throughout, so the subtractions cannot wrap
+        if w > width - x {
+            y += shelf_height;
+            x = 0;
+            shelf_height = 0;
+        }
+        if h > height - y {
+            return None;
+        }
+        positions[i] = (x, y);
+        x += w;
+        shelf_height = shelf_height.max(h);
+    }
+    Some(positions)
+}
+
+/// Copies `image` to `(x + padding, y + padding)` and repeats its edge texels outwards over
+/// the padding.
+fn blit_extruded(canvas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let (w, h) = (image.width() as i64, image.height() as i64);
+    let padding = padding as i64;
+    for dy in -padding..h + padding {
+        for dx in -padding..w + padding {
+            let source = image.get_pixel(dx.clamp(0, w - 1) as u32, dy.clamp(0, h - 1) as u32);
+            let cx = (x as i64 + padding + dx) as u32;
+            let cy = (y as i64 + padding + dy) as u32;
+            canvas.put_pixel(cx, cy, *source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// A `width` x `height` image whose texel (x, y) is `(x, y, tag, 255)`.
+    fn image(width: u32, height: u32, tag: u8) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, tag, 255])))
+    }
+
+    fn overlaps(a: &AtlasRegion, b: &AtlasRegion) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn single_image_fits() {
+        let mut builder = AtlasBuilder::new(64).with_padding(0);
+        builder.add("a", &image(10, 6, 1));
+        let (canvas, regions) = builder.compose().unwrap();
+        assert_eq!(canvas.dimensions(), (16, 8));
+        let region = regions["a"];
+        assert_eq!((region.x, region.y, region.width, region.height), (0, 0, 10, 6));
+        assert_eq!(canvas.get_pixel(9, 5), &Rgba([9, 5, 1, 255]));
+    }
+
+    #[test]
+    fn grows_until_everything_fits() {
+        let mut builder = AtlasBuilder::new(1024).with_padding(0);
+        for (i, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            builder.add(key, &image(16, 16, i as u8));
+        }
+        // 32x16 by area, but a single shelf only holds two of them
+        let (canvas, regions) = builder.compose().unwrap();
+        assert_eq!(canvas.dimensions(), (32, 32));
+        let regions: Vec<&AtlasRegion> = regions.values().collect();
+        for (i, a) in regions.iter().enumerate() {
+            assert!(a.x + a.width <= 32 && a.y + a.height <= 32);
+            assert!(regions[i + 1..].iter().all(|b| !overlaps(a, b)));
+        }
+    }
+
+    #[test]
+    fn too_many_images_are_an_error() {
+        let mut builder = AtlasBuilder::new(16).with_padding(0);
+        builder.add("a", &image(16, 16, 0));
+        builder.add("b", &image(16, 16, 1));
+        assert!(builder.compose().is_err());
+    }
+
+    #[test]
+    fn huge_sizes_do_not_overflow() {
+        let builder = AtlasBuilder::new(u32::MAX);
+        assert!(builder.pack(&[(1 << 31, 1 << 31), (1, 1 << 31)]).is_err());
+        assert!(builder.pack(&[(3 << 30, 1), (1, 1)]).is_err());
+    }
+
+    #[test]
+    fn padding_repeats_the_edge_texels() {
+        let mut builder = AtlasBuilder::new(64).with_padding(1);
+        builder.add("a", &image(2, 2, 7));
+        let (canvas, regions) = builder.compose().unwrap();
+        assert_eq!(canvas.dimensions(), (4, 4));
+        assert_eq!((regions["a"].x, regions["a"].y), (1, 1));
+        for (cx, cy, sx, sy) in [(0, 0, 0, 0), (3, 0, 1, 0), (0, 3, 0, 1), (3, 3, 1, 1), (2, 0, 1, 0), (0, 2, 0, 1)] {
+            assert_eq!(canvas.get_pixel(cx, cy), &Rgba([sx, sy, 7, 255]), "texel {},{}", cx, cy);
+        }
+    }
+
+    #[test]
+    fn remap_flips_rows_into_the_atlas() {
+        let mut builder = AtlasBuilder::new(64).with_padding(0);
+        builder.add("a", &image(10, 6, 0));
+        let (_, regions) = builder.compose().unwrap();
+        let region = regions["a"];
+        // 16x8 atlas, region at the top: its bottom row is 2 texels above v = 0
+        assert_eq!(region.uv_offset, Vec2::new(0.0, 0.25));
+        assert_eq!(region.uv_scale, Vec2::new(0.625, 0.75));
+        assert_eq!(region.remap(Vec2::ZERO), Vec2::new(0.0, 0.25));
+        assert_eq!(region.remap(Vec2::ONE), Vec2::new(0.625, 1.0));
+    }
+}
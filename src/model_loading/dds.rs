@@ -0,0 +1,228 @@
+//! DDS container reader, for both the legacy header (FourCC `DXT1`..`DXT5`, `ATI1`/`ATI2`,
+//! `BC4U`.., plain RGB masks) and the `DX10` extension with DXGI formats, texture arrays and
+//! cube maps. Volume textures are rejected.
+
+use super::texture_data::{PixelFormat, Reader, TextureData};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER: usize = 4;
+const PIXEL_FORMAT: usize = HEADER + 72;
+const DX10_HEADER: usize = HEADER + 124;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+
+/// True when `bytes` start with the DDS magic.
+pub fn is_dds(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<TextureData> {
+    if !is_dds(bytes) {
+        anyhow::bail!("not a DDS file");
+    }
+    let reader = Reader { bytes };
+
+    let height = reader.u32(HEADER + 8)?;
+    let width = reader.u32(HEADER + 12)?;
+    // 0 in files that only hold the base level
+    let mip_count = reader.u32(HEADER + 24)?.max(1);
+    let caps2 = reader.u32(HEADER + 108)?;
+
+    let pf_flags = reader.u32(PIXEL_FORMAT + 4)?;
+    let four_cc = reader.slice(PIXEL_FORMAT + 8, 4)?;
+
+    let (format, srgb, layers, cube, data_offset) = if pf_flags & DDPF_FOURCC != 0 && four_cc == b"DX10" {
+        let dxgi_format = reader.u32(DX10_HEADER)?;
+        let dimension = reader.u32(DX10_HEADER + 4)?;
+        let misc = reader.u32(DX10_HEADER + 8)?;
+        let array_size = reader.u32(DX10_HEADER + 12)?.max(1);
+        if dimension == D3D10_RESOURCE_DIMENSION_TEXTURE3D {
+            anyhow::bail!("3D textures are not supported");
+        }
+        let (format, srgb) = dxgi_to_pixel_format(dxgi_format)
+            .ok_or_else(|| anyhow::anyhow!("unsupported DXGI format {}", dxgi_format))?;
+        // arraySize counts cubes, not faces, for cube maps
+        let layers = if array_size > 1 { array_size } else { 0 };
+        (format, srgb, layers, misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0, DX10_HEADER + 20)
+    } else {
+        if caps2 & DDSCAPS2_VOLUME != 0 {
+            anyhow::bail!("3D textures are not supported");
+        }
+        let format = legacy_pixel_format(&reader, pf_flags, four_cc)?;
+        (format, false, 0, caps2 & DDSCAPS2_CUBEMAP != 0, DX10_HEADER)
+    };
+    let faces = if cube { 6 } else { 1 };
+
+    let mut data = TextureData {
+        format,
+        srgb,
+        width,
+        height,
+        layers,
+        faces,
+        levels: Vec::new(),
+    };
+    data.check_dimensions(mip_count)?;
+    data.levels = vec![Vec::new(); mip_count as usize];
+
+    // DDS stores every image's whole mip chain in turn; regroup it level by level
+    let mut offset = data_offset;
+    for _ in 0..data.images() {
+        for level in 0..mip_count as usize {
+            let (w, h) = data.level_size(level);
+            let size = format.image_size(w, h);
+            data.levels[level].extend_from_slice(reader.slice(offset, size)?);
+            offset += size;
+        }
+    }
+
+    data.validate()?;
+    Ok(data)
+}
+
+fn legacy_pixel_format(reader: &Reader, flags: u32, four_cc: &[u8]) -> anyhow::Result<PixelFormat> {
+    use PixelFormat::*;
+    if flags & DDPF_FOURCC != 0 {
+        return Ok(match four_cc {
+            // DXT2/DXT4 are premultiplied variants with the same block layout
+            b"DXT1" => Bc1Alpha,
+            b"DXT2" | b"DXT3" => Bc2,
+            b"DXT4" | b"DXT5" => Bc3,
+            b"ATI1" | b"BC4U" => Bc4,
+            b"BC4S" => Bc4Signed,
+            b"ATI2" | b"BC5U" => Bc5,
+            b"BC5S" => Bc5Signed,
+            // D3DFMT codes stored in the FourCC field
+            [111, 0, 0, 0] => R16F,
+            [112, 0, 0, 0] => Rg16F,
+            [113, 0, 0, 0] => Rgba16F,
+            [114, 0, 0, 0] => R32F,
+            [115, 0, 0, 0] => Rg32F,
+            [116, 0, 0, 0] => Rgba32F,
+            _ => anyhow::bail!("unsupported DDS FourCC {:?}", String::from_utf8_lossy(four_cc)),
+        });
+    }
+
+    let bit_count = reader.u32(PIXEL_FORMAT + 12)?;
+    let masks = [
+        reader.u32(PIXEL_FORMAT + 16)?,
+        reader.u32(PIXEL_FORMAT + 20)?,
+        reader.u32(PIXEL_FORMAT + 24)?,
+        reader.u32(PIXEL_FORMAT + 28)?,
+    ];
+    let alpha = flags & DDPF_ALPHAPIXELS != 0;
+
+    Ok(match (flags & (DDPF_RGB | DDPF_LUMINANCE), bit_count, masks) {
+        (DDPF_RGB, 32, [0xff, 0xff00, 0xff_0000, _]) if alpha => Rgba8,
+        (DDPF_RGB, 32, [0xff_0000, 0xff00, 0xff, _]) if alpha => Bgra8,
+        (DDPF_RGB, 24, [0xff, 0xff00, 0xff_0000, _]) => Rgb8,
+        (DDPF_RGB, 24, [0xff_0000, 0xff00, 0xff, _]) => Bgr8,
+        (DDPF_LUMINANCE, 8, _) => R8,
+        _ => anyhow::bail!(
+            "unsupported DDS pixel layout ({} bits, masks {:x?})",
+            bit_count, masks
+        ),
+    })
+}
+
+/// (format, is sRGB) for the `DXGI_FORMAT` values this loader understands.
+fn dxgi_to_pixel_format(dxgi_format: u32) -> Option<(PixelFormat, bool)> {
+    use PixelFormat::*;
+    Some(match dxgi_format {
+        2 => (Rgba32F, false),
+        10 => (Rgba16F, false),
+        16 => (Rg32F, false),
+        28 => (Rgba8, false),
+        29 => (Rgba8, true),
+        34 => (Rg16F, false),
+        41 => (R32F, false),
+        49 => (Rg8, false),
+        54 => (R16F, false),
+        61 => (R8, false),
+        71 => (Bc1Alpha, false),
+        72 => (Bc1Alpha, true),
+        74 => (Bc2, false),
+        75 => (Bc2, true),
+        77 => (Bc3, false),
+        78 => (Bc3, true),
+        80 => (Bc4, false),
+        81 => (Bc4Signed, false),
+        83 => (Bc5, false),
+        84 => (Bc5Signed, false),
+        87 => (Bgra8, false),
+        91 => (Bgra8, true),
+        95 => (Bc6hUnsigned, false),
+        96 => (Bc6hSigned, false),
+        98 => (Bc7, false),
+        99 => (Bc7, true),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A legacy DXT1 header for a `width` x `height` texture with `mip_count` levels.
+    fn dxt1_header(width: u32, height: u32, mip_count: u32) -> Vec<u8> {
+        let mut bytes = vec![0; DX10_HEADER];
+        bytes[..4].copy_from_slice(MAGIC);
+        let mut put = |offset: usize, value: u32| bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        put(HEADER, 124);
+        put(HEADER + 8, height);
+        put(HEADER + 12, width);
+        put(HEADER + 24, mip_count);
+        put(PIXEL_FORMAT, 32);
+        put(PIXEL_FORMAT + 4, DDPF_FOURCC);
+        bytes[PIXEL_FORMAT + 8..PIXEL_FORMAT + 12].copy_from_slice(b"DXT1");
+        bytes
+    }
+
+    #[test]
+    fn reads_a_mip_chain() {
+        let mut bytes = dxt1_header(8, 8, 4);
+        // 2x2 blocks, then a single block for each of 4x4, 2x2 and 1x1
+        bytes.extend((0..56).map(|i| i as u8));
+        let data = parse(&bytes).unwrap();
+        assert_eq!(data.format, PixelFormat::Bc1Alpha);
+        assert_eq!(data.levels.iter().map(Vec::len).collect::<Vec<_>>(), [32, 8, 8, 8]);
+        assert_eq!(data.levels[1][0], 32);
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let mut bytes = dxt1_header(8, 8, 4);
+        bytes.extend([0; 55]);
+        assert!(parse(&bytes).is_err());
+        assert!(parse(&bytes[..40]).is_err());
+    }
+
+    #[test]
+    fn oversized_mip_counts_are_errors() {
+        for mip_count in [5, 40, u32::MAX] {
+            let mut bytes = dxt1_header(8, 8, mip_count);
+            bytes.extend([0; 64]);
+            let error = parse(&bytes).unwrap_err().to_string();
+            assert!(error.contains("mip levels"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn oversized_arrays_are_errors() {
+        let mut bytes = dxt1_header(4, 4, 1);
+        bytes[PIXEL_FORMAT + 8..PIXEL_FORMAT + 12].copy_from_slice(b"DX10");
+        let mut dx10 = [0u8; 20];
+        dx10[..4].copy_from_slice(&71u32.to_le_bytes());
+        dx10[8..12].copy_from_slice(&DDS_RESOURCE_MISC_TEXTURECUBE.to_le_bytes());
+        dx10[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend(dx10);
+        assert!(parse(&bytes).is_err());
+    }
+}
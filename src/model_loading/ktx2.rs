@@ -0,0 +1,157 @@
+//! KTX2 container reader (https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html).
+//! Supports BCn and plain 8-bit/float formats without supercompression; Basis Universal and
+//! Zstandard payloads are rejected.
+
+use super::texture_data::{PixelFormat, Reader, TextureData};
+
+const IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const LEVEL_INDEX: usize = 80;
+
+/// True when `bytes` start with the KTX2 identifier.
+pub fn is_ktx2(bytes: &[u8]) -> bool {
+    bytes.starts_with(&IDENTIFIER)
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<TextureData> {
+    if !is_ktx2(bytes) {
+        anyhow::bail!("not a KTX2 file");
+    }
+    let reader = Reader { bytes };
+
+    let vk_format = reader.u32(12)?;
+    let width = reader.u32(20)?;
+    let height = reader.u32(24)?;
+    let depth = reader.u32(28)?;
+    let layers = reader.u32(32)?;
+    let faces = reader.u32(36)?;
+    let level_count = reader.u32(40)?;
+    let supercompression = reader.u32(44)?;
+
+    let (format, srgb) = vk_format_to_pixel_format(vk_format)
+        .ok_or_else(|| anyhow::anyhow!("unsupported KTX2 vkFormat {}", vk_format))?;
+    if supercompression != 0 {
+        anyhow::bail!("KTX2 supercompression scheme {} is not supported", supercompression);
+    }
+    if depth > 1 {
+        anyhow::bail!("3D textures are not supported (depth {})", depth);
+    }
+    if faces != 1 && faces != 6 {
+        anyhow::bail!("KTX2 face count must be 1 or 6, got {}", faces);
+    }
+
+    let mut data = TextureData {
+        format,
+        srgb,
+        width,
+        height,
+        layers,
+        faces,
+        levels: Vec::new(),
+    };
+    data.check_dimensions(level_count.max(1))?;
+
+    // a level count of 0 asks the loader to generate the chain from the base level. Images
+    // inside a level are tightly packed; only whole levels are aligned, through their offsets
+    for level in 0..level_count.max(1) as usize {
+        let entry = LEVEL_INDEX + level * 24;
+        let offset = reader.u64(entry)? as usize;
+        let length = reader.u64(entry + 8)? as usize;
+        data.levels.push(reader.slice(offset, length)?.to_vec());
+    }
+
+    data.validate()?;
+    Ok(data)
+}
+
+/// (format, is sRGB) for the `VkFormat` values this loader understands.
+fn vk_format_to_pixel_format(vk_format: u32) -> Option<(PixelFormat, bool)> {
+    use PixelFormat::*;
+    Some(match vk_format {
+        9 => (R8, false),
+        16 => (Rg8, false),
+        23 => (Rgb8, false),
+        29 => (Rgb8, true),
+        30 => (Bgr8, false),
+        36 => (Bgr8, true),
+        37 => (Rgba8, false),
+        43 => (Rgba8, true),
+        44 => (Bgra8, false),
+        50 => (Bgra8, true),
+        76 => (R16F, false),
+        83 => (Rg16F, false),
+        97 => (Rgba16F, false),
+        100 => (R32F, false),
+        103 => (Rg32F, false),
+        109 => (Rgba32F, false),
+        131 => (Bc1, false),
+        132 => (Bc1, true),
+        133 => (Bc1Alpha, false),
+        134 => (Bc1Alpha, true),
+        135 => (Bc2, false),
+        136 => (Bc2, true),
+        137 => (Bc3, false),
+        138 => (Bc3, true),
+        139 => (Bc4, false),
+        140 => (Bc4Signed, false),
+        141 => (Bc5, false),
+        142 => (Bc5Signed, false),
+        143 => (Bc6hUnsigned, false),
+        144 => (Bc6hSigned, false),
+        145 => (Bc7, false),
+        146 => (Bc7, true),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An RGBA8 file holding `levels` (level 0 first), with `level_count` in the header.
+    fn rgba8(width: u32, height: u32, level_count: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = IDENTIFIER.to_vec();
+        for value in [37, 1, width, height, 0, 0, 1, level_count, 0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.resize(LEVEL_INDEX, 0);
+        let mut offset = LEVEL_INDEX + levels.len() * 24;
+        for level in levels {
+            for value in [offset, level.len(), level.len()] {
+                bytes.extend((value as u64).to_le_bytes());
+            }
+            offset += level.len();
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_levels() {
+        let bytes = rgba8(2, 2, 2, &[&[1; 16], &[2; 4]]);
+        let data = parse(&bytes).unwrap();
+        assert_eq!((data.format, data.srgb, data.width, data.height), (PixelFormat::Rgba8, false, 2, 2));
+        assert_eq!(data.levels, [vec![1; 16], vec![2; 4]]);
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let bytes = rgba8(2, 2, 2, &[&[1; 16], &[2; 4]]);
+        assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(parse(&bytes[..60]).is_err());
+    }
+
+    #[test]
+    fn oversized_headers_are_errors() {
+        for level_count in [3, u32::MAX] {
+            let error = parse(&rgba8(2, 2, level_count, &[&[1; 16], &[2; 4]])).unwrap_err().to_string();
+            assert!(error.contains("mip levels"), "{}", error);
+        }
+
+        let mut bytes = rgba8(2, 2, 1, &[&[1; 16]]);
+        bytes[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        bytes[36..40].copy_from_slice(&6u32.to_le_bytes());
+        assert!(parse(&bytes).is_err());
+    }
+}
@@ -1,44 +1,72 @@
+use super::atlas::AtlasRegion;
 use super::vertex::Vertex;
-use super::texture::Texture;
+use crate::bounds::{Aabb, Frustum, Ray, Sphere};
+use crate::gl_object::{GlBuffer, GlVertexArray};
+use crate::material::Material;
+use crate::shader::Shader;
 
-use gl;
 use std::mem;
+use std::rc::Rc;
 
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
-    pub textures: Vec<Texture>,
+    pub material: Rc<Material>,
 
-    pub vao: u32,
-    pub vbo: u32,
-    pub ebo: u32,
+    /// Model-space bounds, computed once when the mesh is created.
+    pub bounding_box: Aabb,
+    pub bounding_sphere: Sphere,
+
+    pub vao: GlVertexArray,
+    pub vbo: GlBuffer,
+    pub ebo: GlBuffer,
 }
 
 impl Mesh {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, textures: Vec<Texture>) -> Self {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, material: Rc<Material>) -> Self {
+        let positions: Vec<_> = vertices.iter().map(|v| v.position).collect();
+        let bounding_box = Aabb::from_points(positions.iter().copied());
+        let bounding_sphere = Sphere::from_points(&positions);
+
         let mut mesh = Mesh {
             vertices,
             indices,
-            textures,
-            vao: 0,
-            vbo: 0,
-            ebo: 0,
+            material,
+            bounding_box,
+            bounding_sphere,
+            vao: GlVertexArray::new(),
+            vbo: GlBuffer::new(),
+            ebo: GlBuffer::new(),
         };
 
         mesh.setup_mesh();
         mesh
     }
 
-    fn setup_mesh(&mut self) {
+    /// Moves the texture coordinates into `region` of an atlas and updates the vertex buffer,
+    /// so the mesh can be drawn with a material sampling the atlas. Call it once per mesh.
+    pub fn remap_tex_coords(&mut self, region: &AtlasRegion) {
+        for vertex in &mut self.vertices {
+            vertex.tex_coords = region.remap(vertex.tex_coords);
+        }
         unsafe {
-            gl::GenVertexArrays(1, &mut self.vao);
-            gl::GenBuffers(1, &mut self.vbo);
-            gl::GenBuffers(1, &mut self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (self.vertices.len() * mem::size_of::<Vertex>()) as isize,
+                self.vertices.as_ptr() as *const _,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
 
-            gl::BindVertexArray(self.vao);
+    fn setup_mesh(&mut self) {
+        unsafe {
+            gl::BindVertexArray(self.vao.id());
 
             // VBO
-            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo.id());
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 (self.vertices.len() * mem::size_of::<Vertex>()) as isize,
@@ -47,7 +75,7 @@ impl Mesh {
             );
 
             // EBO
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo.id());
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
                 (self.indices.len() * mem::size_of::<u32>()) as isize,
@@ -65,7 +93,7 @@ impl Mesh {
                 gl::FLOAT,
                 gl::FALSE,
                 stride,
-                0 as *const _,
+                std::ptr::null(),
             );
 
             // ---- NORMAL (location = 1)
@@ -94,28 +122,104 @@ impl Mesh {
         }
     }
 
-    pub fn draw(&self, shader_program: u32) {
-    unsafe {
-        if let Some(tex) = self.textures.first() {
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, tex.id);
+    /// Whether any part of the mesh may be inside `frustum`, which must be in model space
+    /// (see `Camera::get_frustum`). The cheap sphere test runs first.
+    pub fn is_visible(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_sphere(&self.bounding_sphere) && frustum.intersects_aabb(&self.bounding_box)
+    }
 
-            let name = std::ffi::CString::new("texture_diffuse1").unwrap();
-            gl::Uniform1i(
-                gl::GetUniformLocation(shader_program, name.as_ptr()),
-                0,
+    /// Closest triangle hit by the model-space `ray` nearer than `max_distance`, as
+    /// `(distance, triangle index, barycentric weights)`. Rays missing the box are rejected
+    /// before any triangle is tested.
+    pub fn intersect(&self, ray: &Ray, max_distance: f32) -> Option<(f32, usize, glam::Vec3)> {
+        match ray.intersect_aabb(&self.bounding_box) {
+            Some(t) if t < max_distance => {}
+            _ => return None,
+        }
+
+        closest_triangle(ray, &self.vertices, &self.indices, max_distance)
+    }
+
+    /// Binds the mesh material on `shader` (which must be in use and compiled with
+    /// `material.defines()`) and draws the mesh.
+    pub fn draw(&self, shader: &Shader) {
+        self.material.bind(shader);
+        self.draw_geometry();
+    }
+
+    /// Issues the draw call alone, for passes that ignore the material (ids, depth).
+    pub fn draw_geometry(&self) {
+        unsafe {
+            gl::BindVertexArray(self.vao.id());
+            gl::DrawElements(
+                gl::TRIANGLES,
+                self.indices.len() as i32,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
             );
+            gl::BindVertexArray(0);
         }
+    }
+}
 
-        gl::BindVertexArray(self.vao);
-        gl::DrawElements(
-            gl::TRIANGLES,
-            self.indices.len() as i32,
-            gl::UNSIGNED_INT,
-            std::ptr::null(),
-        );
-        gl::BindVertexArray(0);
+/// `Mesh::intersect` without the bounding box test: every triangle of `indices` is tried.
+fn closest_triangle(
+    ray: &Ray,
+    vertices: &[Vertex],
+    indices: &[u32],
+    max_distance: f32,
+) -> Option<(f32, usize, glam::Vec3)> {
+    let mut closest = None;
+    let mut max_distance = max_distance;
+    for (triangle_index, triangle) in indices.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position);
+        if let Some((distance, barycentric)) = ray.intersect_triangle(a, b, c)
+            && distance < max_distance
+        {
+            max_distance = distance;
+            closest = Some((distance, triangle_index, barycentric));
+        }
     }
+    closest
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Vec2, Vec3};
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex { position: Vec3::new(x, y, z), normal: Vec3::Z, tex_coords: Vec2::ZERO }
+    }
+
+    /// Two unit quads facing +Z, at z = 0 and z = -1, two triangles each.
+    fn quads() -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        for z in [0.0, -1.0] {
+            vertices.extend([vertex(0.0, 0.0, z), vertex(1.0, 0.0, z), vertex(1.0, 1.0, z), vertex(0.0, 1.0, z)]);
+        }
+        (vertices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7])
+    }
+
+    #[test]
+    fn closest_triangle_wins() {
+        let (vertices, indices) = quads();
+        let ray = Ray { origin: Vec3::new(0.25, 0.75, 2.0), direction: Vec3::NEG_Z };
+        let (distance, triangle, _) = closest_triangle(&ray, &vertices, &indices, f32::INFINITY).unwrap();
+        assert_eq!((distance, triangle), (2.0, 1));
+
+        // coming from behind, the far quad is hit first
+        let back = Ray { origin: Vec3::new(0.75, 0.25, -3.0), direction: Vec3::Z };
+        let (distance, triangle, _) = closest_triangle(&back, &vertices, &indices, f32::INFINITY).unwrap();
+        assert_eq!((distance, triangle), (2.0, 2));
+    }
+
+    #[test]
+    fn max_distance_limits_hits() {
+        let (vertices, indices) = quads();
+        let ray = Ray { origin: Vec3::new(0.25, 0.75, 2.0), direction: Vec3::NEG_Z };
+        assert!(closest_triangle(&ray, &vertices, &indices, 2.0).is_none());
+        let outside = Ray { origin: Vec3::new(2.0, 0.5, 2.0), direction: Vec3::NEG_Z };
+        assert!(closest_triangle(&outside, &vertices, &indices, f32::INFINITY).is_none());
+    }
 }
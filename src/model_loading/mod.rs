@@ -1,4 +1,8 @@
+pub mod atlas;
+pub mod dds;
+pub mod ktx2;
 pub mod mesh;
 pub mod model;
 pub mod texture;
+pub mod texture_data;
 pub mod vertex;
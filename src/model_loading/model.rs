@@ -1,25 +1,81 @@
-use super::{mesh::Mesh, vertex::Vertex};
+use super::{mesh::Mesh, texture::{ColorSpace, Texture, TextureDescriptor}, vertex::Vertex};
+use crate::bounds::{Aabb, Frustum, Ray};
+use crate::material::Material;
+use crate::shader::Shader;
+use crate::vfs;
+use cgmath::Vector3;
 use glam::{Vec2, Vec3};
 
+use anyhow::Context;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::Path;
-use tobj;
+use std::rc::Rc;
+
+/// Meshes drawn and skipped by frustum culling, summed over one or more draws.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+impl std::ops::AddAssign for DrawStats {
+    fn add_assign(&mut self, other: Self) {
+        self.drawn += other.drawn;
+        self.culled += other.culled;
+    }
+}
+
+/// Closest intersection of a ray with a model's triangles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PickHit {
+    /// Along the ray, in the units of its direction.
+    pub distance: f32,
+    pub mesh_index: usize,
+    /// Index of the triangle in the mesh, i.e. the hit indices are `3 * triangle_index..+3`.
+    pub triangle_index: usize,
+    /// Weights of the triangle's three vertices at the hit point.
+    pub barycentric: glam::Vec3,
+}
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub directory: String,
+    /// Material maps that failed to load, one message each; their materials go without them.
+    pub map_errors: Vec<String>,
 }
 
-impl Model {
-    pub fn load(path: &str) -> anyhow::Result<Self> {
-        let (models, _materials) = tobj::load_obj(
-            path,
-            &tobj::LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        )?;
+/// Geometry of one mesh as read from the file, before upload.
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into `ModelData::materials`; `None` gets the default material.
+    pub material: Option<usize>,
+}
+
+/// One material of the MTL file.
+pub struct MaterialData {
+    /// sRGB, as written in the file.
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shininess: f32,
+    /// (shader sampler, file path, descriptor) of every map whose file exists.
+    pub maps: Vec<(&'static str, String, TextureDescriptor)>,
+}
+
+/// What `Model::load` reads from disk, parsed without touching GL so it can be produced on
+/// any thread and uploaded later with `Model::from_data`.
+pub struct ModelData {
+    pub directory: String,
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialData>,
+    /// The OBJ file and the MTL files it pulled in, i.e. what to watch for changes.
+    pub sources: Vec<String>,
+}
 
+impl ModelData {
+    pub fn parse(path: &str) -> anyhow::Result<Self> {
         let directory = Path::new(path)
             .parent()
             .unwrap_or(Path::new(""))
@@ -27,13 +83,39 @@ impl Model {
             .unwrap_or("")
             .to_string();
 
+        let bytes = vfs::read(path).with_context(|| format!("Failed to open model {}", path))?;
+        // same as tobj::load_obj, but reading through the vfs and remembering which MTL files
+        // were read
+        let sources = RefCell::new(vec![path.to_string()]);
+        let (models, obj_materials) = tobj::load_obj_buf(
+            &mut bytes.as_slice(),
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+            |mtl_path| {
+                let mtl_path = Path::new(&directory).join(mtl_path).to_string_lossy().into_owned();
+                sources.borrow_mut().push(mtl_path.clone());
+                let bytes = vfs::read(&mtl_path).map_err(|_| tobj::LoadError::OpenFileFailed)?;
+                tobj::load_mtl_buf(&mut bytes.as_slice())
+            },
+        )
+        .with_context(|| format!("Failed to parse model {}", path))?;
+
+        // an OBJ without a usable .mtl still loads, with the default material on every mesh
+        let materials = obj_materials
+            .unwrap_or_default()
+            .iter()
+            .map(|m| MaterialData::from_obj(m, &directory))
+            .collect();
+
         let mut meshes = Vec::new();
 
         for model in models {
             let mesh = &model.mesh;
 
-            let mut vertices = Vec::new();
-            vertices.reserve(mesh.positions.len() / 3);
+            let mut vertices = Vec::with_capacity(mesh.positions.len() / 3);
 
             for i in 0..mesh.positions.len() / 3 {
                 let pos = Vec3::new(
@@ -65,18 +147,207 @@ impl Model {
                 });
             }
 
-            let indices = mesh.indices.clone();
-            let textures = vec![];
+            meshes.push(MeshData {
+                vertices,
+                indices: mesh.indices.clone(),
+                material: mesh.material_id,
+            });
+        }
+
+        Ok(Self { directory, meshes, materials, sources: sources.into_inner() })
+    }
+
+    /// Every map the materials use, once per (path, colour space).
+    pub fn texture_maps(&self) -> Vec<(String, TextureDescriptor)> {
+        let mut maps: Vec<(String, TextureDescriptor)> = Vec::new();
+        for (_, path, descriptor) in self.materials.iter().flat_map(|m| &m.maps) {
+            if !maps.iter().any(|(p, d)| p == path && d.color_space == descriptor.color_space) {
+                maps.push((path.clone(), *descriptor));
+            }
+        }
+        maps
+    }
+}
+
+impl MaterialData {
+    fn from_obj(m: &tobj::Material, directory: &str) -> Self {
+        let color = |c: Option<[f32; 3]>, default: f32| {
+            c.map(Vector3::from).unwrap_or(Vector3::new(default, default, default))
+        };
 
-            meshes.push(Mesh::new(vertices, indices, textures));
+        let mut maps = Vec::new();
+        for (slot, name, descriptor) in [
+            (Material::DIFFUSE_MAP, &m.diffuse_texture, TextureDescriptor::color()),
+            (Material::SPECULAR_MAP, &m.specular_texture, TextureDescriptor::data()),
+            (Material::NORMAL_MAP, &m.normal_texture, TextureDescriptor::data()),
+        ] {
+            let Some(name) = name else { continue };
+            let path = Path::new(directory).join(name);
+            // a missing map is left out instead of failing the whole model
+            if let Some(path) = path.to_str()
+                && vfs::exists(path)
+            {
+                maps.push((slot, path.to_string(), descriptor));
+            }
         }
 
-        Ok(Self { meshes, directory })
+        Self {
+            ambient: color(m.ambient, 0.2),
+            diffuse: color(m.diffuse, 0.8),
+            specular: color(m.specular, 0.5),
+            shininess: m.shininess.unwrap_or(32.0),
+            maps,
+        }
     }
+}
 
-    pub fn draw(&self, shader_program: u32) {
+/// Creates the texture for a map referenced by a material file.
+pub type TextureLoader<'a> = dyn FnMut(&str, &TextureDescriptor) -> anyhow::Result<Rc<Texture>> + 'a;
+
+impl Model {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        Self::load_with(path, &mut |path, descriptor| {
+            Texture::from_file_with(path, descriptor).map(Rc::new)
+        })
+    }
+
+    /// Like `load`, with the material maps created by `load_texture`, e.g. to share them
+    /// through an `AssetManager`.
+    pub fn load_with(path: &str, load_texture: &mut TextureLoader) -> anyhow::Result<Self> {
+        Ok(Self::from_data(ModelData::parse(path)?, load_texture))
+    }
+
+    /// Uploads parsed model data: the material maps through `load_texture`, then the meshes.
+    pub fn from_data(data: ModelData, load_texture: &mut TextureLoader) -> Self {
+        let (materials, map_errors) = Self::build_materials(&data, load_texture);
+        let default_material = Rc::new(Material::default());
+        let meshes = data
+            .meshes
+            .into_iter()
+            .map(|mesh| Self::build_mesh(mesh, &materials, &default_material))
+            .collect();
+        Self { meshes, directory: data.directory, map_errors }
+    }
+
+    /// A model without meshes, drawn while the real one is loading.
+    pub fn empty() -> Self {
+        Self { meshes: Vec::new(), directory: String::new(), map_errors: Vec::new() }
+    }
+
+    /// Bounding box of every vertex of the model, in model space.
+    pub fn bounding_box(&self) -> Aabb {
+        self.meshes.iter().fold(Aabb::EMPTY, |aabb, m| aabb.union(&m.bounding_box))
+    }
+
+    /// Every `#define` set used by the meshes of this model, one entry per distinct material
+    /// layout, so each shader variant can be built up front.
+    pub fn material_defines(&self) -> Vec<Vec<String>> {
+        let mut defines: Vec<Vec<String>> = self.meshes.iter().map(|m| m.material.defines()).collect();
+        defines.sort();
+        defines.dedup();
+        defines
+    }
+
+    /// Draws every mesh with the same `shader`. Use `Mesh::draw` with a shader variant per
+    /// material when the meshes do not all share one texture layout.
+    pub fn draw(&self, shader: &Shader) {
         for mesh in &self.meshes {
-            mesh.draw(shader_program);
+            mesh.draw(shader);
         }
     }
+
+    /// Like `draw`, but skips meshes entirely outside `frustum` (in model space, see
+    /// `Camera::get_frustum`).
+    pub fn draw_culled(&self, shader: &Shader, frustum: &Frustum) -> DrawStats {
+        self.for_each_visible(frustum, |mesh| mesh.draw(shader))
+    }
+
+    /// Calls `draw` for every mesh not entirely outside `frustum`, for draws that need more
+    /// than one shader (e.g. a `ShaderVariants` variant per material).
+    pub fn for_each_visible(&self, frustum: &Frustum, mut draw: impl FnMut(&Mesh)) -> DrawStats {
+        let mut stats = DrawStats::default();
+        for mesh in &self.meshes {
+            if mesh.is_visible(frustum) {
+                draw(mesh);
+                stats.drawn += 1;
+            } else {
+                stats.culled += 1;
+            }
+        }
+        stats
+    }
+
+    /// ID pass for an `IdBuffer`: draws every mesh with `objectId = first_id + mesh index`
+    /// on `shader` (in use, e.g. `object_id.vs/fs`). Returns the next free id so several
+    /// models can share one pass. `first_id` must not be 0, which marks the background.
+    pub fn draw_ids(&self, shader: &Shader, first_id: u32) -> u32 {
+        let mut id = first_id;
+        for mesh in &self.meshes {
+            shader.set("objectId", id);
+            mesh.draw_geometry();
+            id += 1;
+        }
+        id
+    }
+
+    /// Closest triangle hit by `ray`, which must be in model space: transform a world ray with
+    /// `ray.transformed(&model_matrix.inverse())`. Meshes whose box the ray misses are skipped.
+    pub fn pick(&self, ray: &Ray) -> Option<PickHit> {
+        let mut closest: Option<PickHit> = None;
+
+        for (mesh_index, mesh) in self.meshes.iter().enumerate() {
+            let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            if let Some((distance, triangle_index, barycentric)) = mesh.intersect(ray, max_distance) {
+                closest = Some(PickHit { distance, mesh_index, triangle_index, barycentric });
+            }
+        }
+
+        closest
+    }
+
+    /// Creates one material per `data.materials` entry, in order. A map that fails to load is
+    /// left out instead of failing the whole model; its error is returned with the materials.
+    pub fn build_materials(data: &ModelData, load_texture: &mut TextureLoader) -> (Vec<Rc<Material>>, Vec<String>) {
+        // a texture referenced by several materials is uploaded once per colour space
+        let mut loaded: HashMap<(String, ColorSpace), Rc<Texture>> = HashMap::new();
+        let mut errors = Vec::new();
+
+        let materials = data.materials
+            .iter()
+            .map(|m| {
+                // MTL colours are authored in sRGB like the diffuse maps next to them
+                let mut material = Material::from_srgb(m.ambient, m.diffuse, m.specular, m.shininess);
+
+                for (slot, path, descriptor) in &m.maps {
+                    let key = (path.clone(), descriptor.color_space);
+                    let texture = match loaded.get(&key) {
+                        Some(texture) => texture.clone(),
+                        None => match load_texture(path, descriptor) {
+                            Ok(texture) => {
+                                loaded.insert(key, texture.clone());
+                                texture
+                            }
+                            Err(e) => {
+                                errors.push(format!("{:#}", e));
+                                continue;
+                            }
+                        },
+                    };
+                    material = material.with_texture(slot, texture);
+                }
+
+                Rc::new(material)
+            })
+            .collect();
+        (materials, errors)
+    }
+
+    /// Uploads one mesh with its material from `materials` (built by `build_materials`).
+    pub fn build_mesh(mesh: MeshData, materials: &[Rc<Material>], default_material: &Rc<Material>) -> Mesh {
+        let material = mesh
+            .material
+            .and_then(|id| materials.get(id).cloned())
+            .unwrap_or_else(|| default_material.clone());
+        Mesh::new(mesh.vertices, mesh.indices, material)
+    }
 }
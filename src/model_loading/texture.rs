@@ -1,33 +1,512 @@
-use image::GenericImageView;
-use gl;
+use super::texture_data::{GlUpload, PixelFormat, TextureData};
+use super::{dds, ktx2};
+use crate::color::srgb_to_linear;
+use crate::gl_object::GlTexture;
+use crate::sampler::SamplerDescriptor;
+use crate::vfs;
+
+use anyhow::Context;
+use image::DynamicImage;
+use std::cell::{Cell, RefCell};
+
+/// Whether a texture gets a mip chain when it is created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mipmaps {
+    /// Generated on the GPU from the base level.
+    Generate,
+    /// Base level only; the texture stays complete whatever the sampler's mipmap filter.
+    BaseOnly,
+}
+
+/// How the texel values of a texture are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Colour maps (diffuse, albedo, emissive) as painted: stored sRGB and decoded to linear
+    /// by the GPU when sampled.
+    Srgb,
+    /// Data maps (normals, specular, roughness, masks), used as is.
+    Linear,
+}
+
+/// Everything that decides how a texture is created and sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureDescriptor {
+    pub color_space: ColorSpace,
+    pub mipmaps: Mipmaps,
+    /// Stored in the texture object; a `Sampler` bound to the same unit overrides it.
+    pub sampler: SamplerDescriptor,
+}
+
+impl TextureDescriptor {
+    /// For colour maps: sRGB, mipmapped, default sampling.
+    pub fn color() -> Self {
+        Self { color_space: ColorSpace::Srgb, ..Self::default() }
+    }
+
+    /// For data maps: linear, mipmapped, default sampling.
+    pub fn data() -> Self {
+        Self { color_space: ColorSpace::Linear, ..Self::default() }
+    }
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Linear,
+            mipmaps: Mipmaps::Generate,
+            sampler: SamplerDescriptor::default(),
+        }
+    }
+}
+
+/// What a texture currently holds; changes when the texture is `replace`d.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureInfo {
+    /// `TEXTURE_2D`, `TEXTURE_2D_ARRAY`, `TEXTURE_CUBE_MAP` or `TEXTURE_CUBE_MAP_ARRAY`.
+    pub target: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Array layers (cubes for cube map arrays), 1 for textures that are not arrays.
+    pub layers: u32,
+    /// Mip levels present, base level included.
+    pub mip_levels: u32,
+    /// Channels stored: 1 (R), 2 (RG), 3 (RGB) or 4 (RGBA). Matches the source image except
+    /// for 8-bit grey sRGB images, which are widened to RGB(A) to get an sRGB format.
+    pub channels: u8,
+    pub color_space: ColorSpace,
+}
+
+/// A file read and decoded on the CPU, not yet uploaded. Can be produced on any thread.
+pub enum TextureSource {
+    Image(DynamicImage),
+    Data(TextureData),
+}
+
+impl TextureSource {
+    /// Reads KTX2 and DDS containers as stored and decodes anything else with `image`.
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        let bytes = vfs::read(path).with_context(|| format!("Failed to load texture {}", path))?;
+        Self::from_bytes(&bytes).with_context(|| format!("Failed to load texture {}", path))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(if ktx2::is_ktx2(bytes) {
+            Self::Data(ktx2::parse(bytes)?)
+        } else if dds::is_dds(bytes) {
+            Self::Data(dds::parse(bytes)?)
+        } else {
+            Self::Image(image::load_from_memory(bytes)?)
+        })
+    }
+}
 
 pub struct Texture {
-    pub id: u32,
     pub type_: String,
     pub path: String,
+    // swapped as a whole by `replace`, so holders of the texture see the new contents
+    object: RefCell<GlTexture>,
+    info: Cell<TextureInfo>,
 }
 
 impl Texture {
-    pub fn from_file(path: &str) -> Self {
-        let img = image::open(path).expect("Failed to load texture");
-        let data = img.flipv().into_rgb8();
-        let (width, height) = img.dimensions();
-        let mut id = 0; // Texture ID 
+    fn new(object: GlTexture, info: TextureInfo) -> Self {
+        Self {
+            type_: "TextureDiffuse".into(),
+            path: String::new(),
+            object: RefCell::new(object),
+            info: Cell::new(info),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.object.borrow().id()
+    }
+
+    pub fn info(&self) -> TextureInfo {
+        self.info.get()
+    }
+
+    pub fn target(&self) -> u32 {
+        self.info.get().target
+    }
+
+    /// Takes over the GL texture and contents of `other`, deleting the current ones, while
+    /// keeping `path` and `type_`. Everything holding this texture (materials, handles) uses
+    /// the new contents from the next bind: this is how placeholders and reloads are swapped in.
+    pub fn replace(&self, other: Texture) {
+        self.info.set(other.info.get());
+        self.object.replace(other.object.into_inner());
+    }
+
+    /// 1x1 opaque white, drawn in place of textures that are still loading.
+    pub fn placeholder() -> Self {
+        let white = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+        let descriptor = TextureDescriptor { mipmaps: Mipmaps::BaseOnly, ..TextureDescriptor::default() };
+        Self::from_image(&white, &descriptor).expect("1x1 placeholder upload cannot fail")
+    }
+
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Self::from_file_with(path, &TextureDescriptor::default())
+    }
+
+    /// Loads KTX2 and DDS containers as stored (see `from_data`) and decodes anything else
+    /// with the `image` crate (see `from_image`).
+    pub fn from_file_with(path: &str, descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        let source = TextureSource::from_file(path)?;
+        let mut texture = Self::from_source(&source, descriptor)
+            .with_context(|| format!("Failed to upload texture {}", path))?;
+        texture.path = path.into();
+        Ok(texture)
+    }
+
+    pub fn from_source(source: &TextureSource, descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        match source {
+            TextureSource::Image(img) => Self::from_image(img, descriptor),
+            TextureSource::Data(data) => Self::from_data(data, descriptor),
+        }
+    }
+
+    /// Uploads `img` keeping its channel count and bit depth (8-bit, 16-bit or float). Rows are
+    /// flipped so the first image row ends up at `v = 1`, as OpenGL expects.
+    ///
+    /// With `ColorSpace::Srgb`, 8-bit RGB(A) uses the `SRGB8` formats and 16-bit images are
+    /// decoded to linear on the CPU, since GL has no 16-bit sRGB formats. Float images are
+    /// linear already.
+    pub fn from_image(img: &DynamicImage, descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        let srgb = descriptor.color_space == ColorSpace::Srgb;
+        let flipped = match img {
+            DynamicImage::ImageLuma8(_) if srgb => DynamicImage::ImageRgb8(img.to_rgb8()).flipv(),
+            DynamicImage::ImageLumaA8(_) if srgb => DynamicImage::ImageRgba8(img.to_rgba8()).flipv(),
+            _ => img.flipv(),
+        };
+        let flipped = if srgb { linearize_16_bit(flipped) } else { flipped };
+        let (width, height) = (flipped.width(), flipped.height());
+        if width == 0 || height == 0 {
+            anyhow::bail!("texture has no pixels ({}x{})", width, height);
+        }
 
-        // unsafe block to make a call to OpenGL(functions) 
-        unsafe{
-            gl::GenTextures(1, &mut id);
-            gl::BindTexture(gl::TEXTURE_2D, id);
+        // (internal format, format, type, channels, bytes per channel, pixel data)
+        let (internal_format, format, type_, channels, channel_size, pixels): (u32, u32, u32, u8, usize, &[u8]) =
+            match &flipped {
+                DynamicImage::ImageLuma8(i) => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, 1, 1, i.as_raw()),
+                DynamicImage::ImageLumaA8(i) => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, 2, 1, i.as_raw()),
+                DynamicImage::ImageRgb8(i) if srgb => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE, 3, 1, i.as_raw()),
+                DynamicImage::ImageRgba8(i) if srgb => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE, 4, 1, i.as_raw()),
+                DynamicImage::ImageRgb8(i) => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, 3, 1, i.as_raw()),
+                DynamicImage::ImageRgba8(i) => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, 4, 1, i.as_raw()),
+                DynamicImage::ImageLuma16(i) => (gl::R16, gl::RED, gl::UNSIGNED_SHORT, 1, 2, as_bytes(i.as_raw())),
+                DynamicImage::ImageLumaA16(i) => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT, 2, 2, as_bytes(i.as_raw())),
+                DynamicImage::ImageRgb16(i) => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, 3, 2, as_bytes(i.as_raw())),
+                DynamicImage::ImageRgba16(i) => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, 4, 2, as_bytes(i.as_raw())),
+                DynamicImage::ImageRgb32F(i) => (gl::RGB32F, gl::RGB, gl::FLOAT, 3, 4, as_bytes(i.as_raw())),
+                DynamicImage::ImageRgba32F(i) => (gl::RGBA32F, gl::RGBA, gl::FLOAT, 4, 4, as_bytes(i.as_raw())),
+                // layouts added to `image` later: fall back to 8-bit RGBA
+                _ => return Self::from_image(&DynamicImage::ImageRgba8(img.to_rgba8()), descriptor),
+            };
 
+        let row_bytes = width as usize * channels as usize * channel_size;
+        let object = GlTexture::new();
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, object.id());
+
+            // rows are tightly packed: use the largest alignment that divides a row
+            let alignment = [8, 4, 2, 1].into_iter().find(|a| row_bytes.is_multiple_of(*a)).unwrap_or(1);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment as i32);
             gl::TexImage2D(
-                gl::TEXTURE_2D, 0, gl::RGB as i32, 
-                width as i32, height as i32, 0, 
-                gl::RGBA, gl::UNSIGNED_BYTE, data.as_ptr() as *const _,
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                format,
+                type_,
+                pixels.as_ptr() as *const _,
             );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            // grey and grey+alpha images sample as (l, l, l, 1) and (l, l, l, a)
+            let swizzle = match channels {
+                1 => Some([gl::RED, gl::RED, gl::RED, gl::ONE]),
+                2 => Some([gl::RED, gl::RED, gl::RED, gl::GREEN]),
+                _ => None,
+            };
+            if let Some(swizzle) = swizzle {
+                let swizzle = swizzle.map(|c| c as i32);
+                gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            }
+
+            descriptor.sampler.apply_to_texture(gl::TEXTURE_2D);
+            match descriptor.mipmaps {
+                Mipmaps::Generate => gl::GenerateMipmap(gl::TEXTURE_2D),
+                Mipmaps::BaseOnly => gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0),
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(Self::new(object, TextureInfo {
+            target: gl::TEXTURE_2D,
+            width,
+            height,
+            layers: 1,
+            mip_levels: match descriptor.mipmaps {
+                Mipmaps::Generate => width.max(height).ilog2() + 1,
+                Mipmaps::BaseOnly => 1,
+            },
+            channels,
+            color_space: descriptor.color_space,
+        }))
+    }
+
+    /// Stacks same-sized images into a `TEXTURE_2D_ARRAY`, layer `i` holding `images[i]`, so
+    /// meshes using different images can share one binding and pick their layer in the
+    /// shader (`sampler2DArray`, `texture(map, vec3(uv, layer))`).
+    ///
+    /// Layers are stored as 8-bit RGBA (sRGB for `ColorSpace::Srgb`) and flipped like
+    /// `from_image`.
+    pub fn array_from_images(images: &[DynamicImage], descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        let Some(first) = images.first() else {
+            anyhow::bail!("texture array has no layers");
+        };
+        let (width, height) = (first.width(), first.height());
+        if width == 0 || height == 0 {
+            anyhow::bail!("texture has no pixels ({}x{})", width, height);
+        }
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4 * images.len());
+        for (layer, img) in images.iter().enumerate() {
+            if (img.width(), img.height()) != (width, height) {
+                anyhow::bail!(
+                    "layer {} is {}x{}, the array is {}x{}",
+                    layer, img.width(), img.height(), width, height
+                );
+            }
+            pixels.extend_from_slice(img.flipv().to_rgba8().as_raw());
         }
 
-        Self {id, type_:  "TextureDiffuse".into(), path: path.into()}
+        let data = TextureData {
+            format: PixelFormat::Rgba8,
+            srgb: false,
+            width,
+            height,
+            layers: images.len() as u32,
+            faces: 1,
+            levels: vec![pixels],
+        };
+        Self::from_data(&data, descriptor)
+    }
 
+    /// `array_from_images` for files, layer `i` read from `paths[i]`.
+    pub fn array_from_files(paths: &[&str], descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        let images = paths
+            .iter()
+            .map(|path| {
+                vfs::read(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|bytes| Ok(image::load_from_memory(&bytes)?))
+                    .with_context(|| format!("Failed to load texture {}", path))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut texture = Self::array_from_images(&images, descriptor)
+            .with_context(|| format!("Failed to upload texture array {}", paths.join(", ")))?;
+        texture.path = paths.join(";");
+        Ok(texture)
     }
-}
\ No newline at end of file
+
+    /// Uploads container data as stored: block-compressed formats stay compressed in VRAM and
+    /// the file's mip levels are used instead of generated ones. Only single-level uncompressed
+    /// data gets a chain generated when `descriptor.mipmaps` asks for one.
+    ///
+    /// Arrays become `TEXTURE_2D_ARRAY`, cube maps `TEXTURE_CUBE_MAP` and arrays of cubes
+    /// `TEXTURE_CUBE_MAP_ARRAY`. Rows are not flipped: export with a flipped V (`toktx
+    /// --lower_left_maps_to_s0t0`, `texconv -vflip`) to match textures read through `image`.
+    ///
+    /// The texture is sRGB if the file says so or `descriptor` asks for it and the format has
+    /// an sRGB variant.
+    pub fn from_data(data: &TextureData, descriptor: &TextureDescriptor) -> anyhow::Result<Self> {
+        data.validate()?;
+        let srgb = data.format.has_srgb() && (data.srgb || descriptor.color_space == ColorSpace::Srgb);
+        let gl_format = data.format.gl_format(srgb);
+
+        let target = match (data.layers, data.faces) {
+            (0, 6) => gl::TEXTURE_CUBE_MAP,
+            (_, 6) => gl::TEXTURE_CUBE_MAP_ARRAY,
+            (0, _) => gl::TEXTURE_2D,
+            _ => gl::TEXTURE_2D_ARRAY,
+        };
+        let generate = descriptor.mipmaps == Mipmaps::Generate
+            && data.levels.len() == 1
+            && !data.format.is_compressed();
+        let object = GlTexture::new();
+
+        unsafe {
+            // errors from earlier calls must not be blamed on this upload
+            while gl::GetError() != gl::NO_ERROR {}
+
+            gl::BindTexture(target, object.id());
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            for (level, bytes) in data.levels.iter().enumerate() {
+                let (width, height) = data.level_size(level);
+                let image_size = bytes.len() / data.images() as usize;
+                let level = level as i32;
+
+                match target {
+                    gl::TEXTURE_2D => upload_2d(target, level, gl_format, (width, height), bytes),
+                    gl::TEXTURE_CUBE_MAP => {
+                        for (face, image) in bytes.chunks_exact(image_size).enumerate() {
+                            let face_target = gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32;
+                            upload_2d(face_target, level, gl_format, (width, height), image);
+                        }
+                    }
+                    // cube map arrays count layer-faces, in the same order as the data
+                    _ => upload_3d(target, level, gl_format, (width, height, data.images()), bytes),
+                }
+            }
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            descriptor.sampler.apply_to_texture(target);
+            if generate {
+                gl::GenerateMipmap(target);
+            } else {
+                gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, data.levels.len() as i32 - 1);
+            }
+            gl::BindTexture(target, 0);
+
+            let error = gl::GetError();
+            if error != gl::NO_ERROR {
+                anyhow::bail!(
+                    "GL error 0x{:x} uploading {:?}; the format may need a newer context or an extension",
+                    error, data.format
+                );
+            }
+        }
+
+        Ok(Self::new(object, TextureInfo {
+            target,
+            width: data.width,
+            height: data.height,
+            layers: data.layers.max(1),
+            mip_levels: if generate {
+                data.width.max(data.height).ilog2() + 1
+            } else {
+                data.levels.len() as u32
+            },
+            channels: data.format.channels(),
+            color_space: if srgb { ColorSpace::Srgb } else { ColorSpace::Linear },
+        }))
+    }
+
+    /// Replaces the sampling state stored in the texture object.
+    pub fn set_sampler(&self, sampler: &SamplerDescriptor) {
+        unsafe {
+            gl::BindTexture(self.target(), self.id());
+            sampler.apply_to_texture(self.target());
+            gl::BindTexture(self.target(), 0);
+        }
+    }
+}
+
+/// Uploads one level of a 2D texture or cube face bound to `target`.
+unsafe fn upload_2d(
+    target: u32,
+    level: i32,
+    (internal_format, upload): (u32, GlUpload),
+    (width, height): (u32, u32),
+    bytes: &[u8],
+) {
+    unsafe {
+        match upload {
+            GlUpload::Compressed => gl::CompressedTexImage2D(
+                target,
+                level,
+                internal_format,
+                width as i32,
+                height as i32,
+                0,
+                bytes.len() as i32,
+                bytes.as_ptr() as *const _,
+            ),
+            GlUpload::Pixels(format, type_) => gl::TexImage2D(
+                target,
+                level,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                format,
+                type_,
+                bytes.as_ptr() as *const _,
+            ),
+        }
+    }
+}
+
+/// Uploads one level of every layer of an array texture bound to `target`.
+unsafe fn upload_3d(
+    target: u32,
+    level: i32,
+    (internal_format, upload): (u32, GlUpload),
+    (width, height, depth): (u32, u32, u32),
+    bytes: &[u8],
+) {
+    unsafe {
+        match upload {
+            GlUpload::Compressed => gl::CompressedTexImage3D(
+                target,
+                level,
+                internal_format,
+                width as i32,
+                height as i32,
+                depth as i32,
+                0,
+                bytes.len() as i32,
+                bytes.as_ptr() as *const _,
+            ),
+            GlUpload::Pixels(format, type_) => gl::TexImage3D(
+                target,
+                level,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                depth as i32,
+                0,
+                format,
+                type_,
+                bytes.as_ptr() as *const _,
+            ),
+        }
+    }
+}
+
+/// Decodes the colour channels (not alpha) of a 16-bit sRGB image to linear; other images are
+/// returned unchanged.
+fn linearize_16_bit(mut img: DynamicImage) -> DynamicImage {
+    let (channels, color_channels) = match img.color() {
+        image::ColorType::L16 => (1, 1),
+        image::ColorType::La16 => (2, 1),
+        image::ColorType::Rgb16 => (3, 3),
+        image::ColorType::Rgba16 => (4, 3),
+        _ => return img,
+    };
+    let data: &mut [u16] = match &mut img {
+        DynamicImage::ImageLuma16(i) => i,
+        DynamicImage::ImageLumaA16(i) => i,
+        DynamicImage::ImageRgb16(i) => i,
+        DynamicImage::ImageRgba16(i) => i,
+        _ => return img,
+    };
+    for pixel in data.chunks_exact_mut(channels) {
+        for c in &mut pixel[..color_channels] {
+            let linear = srgb_to_linear(*c as f32 / 65535.0);
+            *c = (linear * 65535.0).round() as u16;
+        }
+    }
+    img
+}
+
+/// Raw bytes of 16-bit or float pixel data, in native byte order as GL reads them.
+fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
@@ -0,0 +1,281 @@
+// EXT_texture_compression_s3tc / EXT_texture_sRGB; the core bindings leave S3TC out
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: u32 = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: u32 = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: u32 = 0x8C4F;
+
+/// Largest width or height accepted from a file, well above what GL implementations allow,
+/// so that sizes computed from the header cannot overflow.
+const MAX_DIMENSION: u32 = 1 << 16;
+
+/// Texel layouts that KTX2 and DDS files are read into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Bgr8,
+    Rgba8,
+    Bgra8,
+    R16F,
+    Rg16F,
+    Rgba16F,
+    R32F,
+    Rg32F,
+    Rgba32F,
+    /// BC1 (DXT1) without alpha.
+    Bc1,
+    /// BC1 (DXT1) with 1-bit alpha.
+    Bc1Alpha,
+    /// BC2 (DXT3).
+    Bc2,
+    /// BC3 (DXT5).
+    Bc3,
+    Bc4,
+    Bc4Signed,
+    Bc5,
+    Bc5Signed,
+    Bc6hUnsigned,
+    Bc6hSigned,
+    Bc7,
+}
+
+/// How a level is handed to GL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GlUpload {
+    /// `glCompressedTexImage*` with the internal format alone.
+    Compressed,
+    /// `glTexImage*` with this (format, type) pair.
+    Pixels(u32, u32),
+}
+
+impl PixelFormat {
+    pub fn is_compressed(self) -> bool {
+        self.block_bytes().0 == 4
+    }
+
+    /// (block edge in texels, bytes per block): 4x4 blocks for BCn, single texels otherwise.
+    pub fn block_bytes(self) -> (u32, usize) {
+        use PixelFormat::*;
+        match self {
+            R8 => (1, 1),
+            Rg8 | R16F => (1, 2),
+            Rgb8 | Bgr8 => (1, 3),
+            Rgba8 | Bgra8 | Rg16F | R32F => (1, 4),
+            Rgba16F | Rg32F => (1, 8),
+            Rgba32F => (1, 16),
+            Bc1 | Bc1Alpha | Bc4 | Bc4Signed => (4, 8),
+            Bc2 | Bc3 | Bc5 | Bc5Signed | Bc6hUnsigned | Bc6hSigned | Bc7 => (4, 16),
+        }
+    }
+
+    /// Bytes taken by one `width` x `height` image of this format, without row padding.
+    pub fn image_size(self, width: u32, height: u32) -> usize {
+        let (block, bytes) = self.block_bytes();
+        let blocks_x = width.div_ceil(block) as usize;
+        let blocks_y = height.div_ceil(block) as usize;
+        blocks_x * blocks_y * bytes
+    }
+
+    /// Channels the format stores.
+    pub fn channels(self) -> u8 {
+        use PixelFormat::*;
+        match self {
+            R8 | R16F | R32F | Bc4 | Bc4Signed => 1,
+            Rg8 | Rg16F | Rg32F | Bc5 | Bc5Signed => 2,
+            Rgb8 | Bgr8 | Bc1 | Bc6hUnsigned | Bc6hSigned => 3,
+            Rgba8 | Bgra8 | Rgba16F | Rgba32F | Bc1Alpha | Bc2 | Bc3 | Bc7 => 4,
+        }
+    }
+
+    /// Whether an sRGB variant exists, i.e. whether `ColorSpace::Srgb` changes anything.
+    pub fn has_srgb(self) -> bool {
+        use PixelFormat::*;
+        matches!(self, Rgb8 | Bgr8 | Rgba8 | Bgra8 | Bc1 | Bc1Alpha | Bc2 | Bc3 | Bc7)
+    }
+
+    /// (internal format, upload) for GL; `srgb` is ignored where `has_srgb` is false.
+    pub fn gl_format(self, srgb: bool) -> (u32, GlUpload) {
+        use GlUpload::*;
+        use PixelFormat::*;
+        let pick = |linear, srgb_format| if srgb { srgb_format } else { linear };
+        match self {
+            R8 => (gl::R8, Pixels(gl::RED, gl::UNSIGNED_BYTE)),
+            Rg8 => (gl::RG8, Pixels(gl::RG, gl::UNSIGNED_BYTE)),
+            Rgb8 => (pick(gl::RGB8, gl::SRGB8), Pixels(gl::RGB, gl::UNSIGNED_BYTE)),
+            Bgr8 => (pick(gl::RGB8, gl::SRGB8), Pixels(gl::BGR, gl::UNSIGNED_BYTE)),
+            Rgba8 => (pick(gl::RGBA8, gl::SRGB8_ALPHA8), Pixels(gl::RGBA, gl::UNSIGNED_BYTE)),
+            Bgra8 => (pick(gl::RGBA8, gl::SRGB8_ALPHA8), Pixels(gl::BGRA, gl::UNSIGNED_BYTE)),
+            R16F => (gl::R16F, Pixels(gl::RED, gl::HALF_FLOAT)),
+            Rg16F => (gl::RG16F, Pixels(gl::RG, gl::HALF_FLOAT)),
+            Rgba16F => (gl::RGBA16F, Pixels(gl::RGBA, gl::HALF_FLOAT)),
+            R32F => (gl::R32F, Pixels(gl::RED, gl::FLOAT)),
+            Rg32F => (gl::RG32F, Pixels(gl::RG, gl::FLOAT)),
+            Rgba32F => (gl::RGBA32F, Pixels(gl::RGBA, gl::FLOAT)),
+            Bc1 => (pick(COMPRESSED_RGB_S3TC_DXT1, COMPRESSED_SRGB_S3TC_DXT1), Compressed),
+            Bc1Alpha => (pick(COMPRESSED_RGBA_S3TC_DXT1, COMPRESSED_SRGB_ALPHA_S3TC_DXT1), Compressed),
+            Bc2 => (pick(COMPRESSED_RGBA_S3TC_DXT3, COMPRESSED_SRGB_ALPHA_S3TC_DXT3), Compressed),
+            Bc3 => (pick(COMPRESSED_RGBA_S3TC_DXT5, COMPRESSED_SRGB_ALPHA_S3TC_DXT5), Compressed),
+            Bc4 => (gl::COMPRESSED_RED_RGTC1, Compressed),
+            Bc4Signed => (gl::COMPRESSED_SIGNED_RED_RGTC1, Compressed),
+            Bc5 => (gl::COMPRESSED_RG_RGTC2, Compressed),
+            Bc5Signed => (gl::COMPRESSED_SIGNED_RG_RGTC2, Compressed),
+            Bc6hUnsigned => (gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, Compressed),
+            Bc6hSigned => (gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, Compressed),
+            Bc7 => (pick(gl::COMPRESSED_RGBA_BPTC_UNORM, gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM), Compressed),
+        }
+    }
+}
+
+/// Texture contents read from a container file, ready to upload as stored: block-compressed
+/// or raw texels, with every mip level, array layer and cube face.
+#[derive(Clone, Debug)]
+pub struct TextureData {
+    pub format: PixelFormat,
+    /// The file marks the data as sRGB-encoded.
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    /// Array layers, 0 for a texture that is not an array.
+    pub layers: u32,
+    /// 6 for cube maps, 1 otherwise.
+    pub faces: u32,
+    /// Level 0 first. Each level holds its images back to back, layer by layer and face by
+    /// face within a layer (`+X, -X, +Y, -Y, +Z, -Z`).
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Most mip levels a `width` x `height` texture can have, down to 1x1.
+pub fn max_levels(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+impl TextureData {
+    /// Size of mip level `level`, never below 1x1.
+    pub fn level_size(&self, level: usize) -> (u32, u32) {
+        let level = u32::try_from(level).unwrap_or(u32::MAX);
+        let shift = |size: u32| size.checked_shr(level).unwrap_or(0).max(1);
+        (shift(self.width), shift(self.height))
+    }
+
+    /// Number of layer/face images in every level (saturating; `check_dimensions` rejects
+    /// counts that overflow).
+    pub fn images(&self) -> u32 {
+        self.layers.max(1).saturating_mul(self.faces)
+    }
+
+    /// Checks the header fields alone, before any level is read, against `level_count`
+    /// levels: after this, level sizes and image counts derived from them do not overflow.
+    pub fn check_dimensions(&self, level_count: u32) -> anyhow::Result<()> {
+        if self.width == 0 || self.height == 0 {
+            anyhow::bail!("texture has no pixels ({}x{})", self.width, self.height);
+        }
+        if self.width > MAX_DIMENSION || self.height > MAX_DIMENSION {
+            anyhow::bail!("texture is too large ({}x{})", self.width, self.height);
+        }
+        if self.faces == 6 && self.width != self.height {
+            anyhow::bail!("cube map faces are not square ({}x{})", self.width, self.height);
+        }
+        if self.layers.max(1).checked_mul(self.faces).is_none() {
+            anyhow::bail!("too many images ({} layers x {} faces)", self.layers, self.faces);
+        }
+        if level_count == 0 {
+            anyhow::bail!("texture has no mip levels");
+        }
+        let max = max_levels(self.width, self.height);
+        if level_count > max {
+            anyhow::bail!(
+                "{} mip levels for a {}x{} texture, which has at most {}",
+                level_count, self.width, self.height, max
+            );
+        }
+        Ok(())
+    }
+
+    /// Checks that the level sizes match the format and dimensions, so uploads never read
+    /// past a level.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.check_dimensions(u32::try_from(self.levels.len()).unwrap_or(u32::MAX))?;
+        for (level, data) in self.levels.iter().enumerate() {
+            let (width, height) = self.level_size(level);
+            let expected = self.format.image_size(width, height).checked_mul(self.images() as usize);
+            if Some(data.len()) != expected {
+                anyhow::bail!(
+                    "mip level {} holds {} bytes, {:?} {}x{} x{} needs {:?}",
+                    level, data.len(), self.format, width, height, self.images(), expected
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Little-endian reads with bounds checks, shared by the container parsers.
+pub(crate) struct Reader<'a> {
+    pub bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    pub fn slice(&self, offset: usize, len: usize) -> anyhow::Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| anyhow::anyhow!("file truncated: {} bytes at offset {} missing", len, offset))
+    }
+
+    pub fn u32(&self, offset: usize) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.slice(offset, 4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&self, offset: usize) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.slice(offset, 8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(width: u32, height: u32, levels: Vec<Vec<u8>>) -> TextureData {
+        TextureData { format: PixelFormat::Rgba8, srgb: false, width, height, layers: 0, faces: 1, levels }
+    }
+
+    #[test]
+    fn level_counts_stop_at_one_texel() {
+        assert_eq!(max_levels(1, 1), 1);
+        assert_eq!(max_levels(8, 2), 4);
+        assert_eq!(max_levels(5, 3), 3);
+        assert_eq!(rgba(8, 2, Vec::new()).level_size(3), (1, 1));
+        assert_eq!(rgba(8, 2, Vec::new()).level_size(40), (1, 1));
+    }
+
+    #[test]
+    fn validate_accepts_a_full_chain() {
+        let data = rgba(4, 2, vec![vec![0; 32], vec![0; 8], vec![0; 4]]);
+        data.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_rejects_bad_layouts() {
+        assert!(rgba(4, 2, vec![vec![0; 31]]).validate().is_err());
+        assert!(rgba(4, 2, Vec::new()).validate().is_err());
+        assert!(rgba(0, 2, vec![Vec::new()]).validate().is_err());
+        assert!(rgba(2, 2, vec![vec![0; 16], vec![0; 4], vec![0; 4]]).validate().is_err());
+
+        let mut cube = rgba(4, 2, vec![vec![0; 32 * 6]]);
+        cube.faces = 6;
+        assert!(cube.validate().is_err());
+    }
+
+    #[test]
+    fn image_counts_that_overflow_are_rejected() {
+        let mut data = rgba(4, 4, vec![Vec::new()]);
+        data.layers = u32::MAX;
+        data.faces = 6;
+        assert!(data.check_dimensions(1).is_err());
+    }
+}
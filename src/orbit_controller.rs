@@ -0,0 +1,135 @@
+use cgmath::{Angle, Deg, InnerSpace, Point3, Vector3};
+
+use crate::bounds::Aabb;
+use crate::camera::{Camera, Projection};
+
+/// Drives a `Camera` around a target point, for inspecting models: rotate, pan and zoom
+/// change a goal state and `update` eases the camera towards it.
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    /// Degrees, same convention as `Camera::yaw`/`Camera::pitch` (the direction the camera looks).
+    pub yaw: f32,
+    pub pitch: f32,
+
+    goal_target: Point3<f32>,
+    goal_distance: f32,
+    goal_yaw: f32,
+    goal_pitch: f32,
+
+    /// Degrees per pixel of mouse motion.
+    pub rotate_sensitivity: f32,
+    /// Fraction of the distance per pixel of mouse motion.
+    pub pan_sensitivity: f32,
+    /// Fraction of the distance per scroll line.
+    pub zoom_sensitivity: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// How quickly the camera catches up with the goal, per second. 0 snaps immediately.
+    pub damping: f32,
+}
+
+impl OrbitController {
+
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: -90.0,
+            pitch: 0.0,
+            goal_target: target,
+            goal_distance: distance,
+            goal_yaw: -90.0,
+            goal_pitch: 0.0,
+            rotate_sensitivity: 0.3,
+            pan_sensitivity: 0.002,
+            zoom_sensitivity: 0.1,
+            min_distance: 0.05,
+            max_distance: 1000.0,
+            damping: 12.0,
+        }
+    }
+
+    /// Orbits around the target; `dx`/`dy` are mouse deltas in pixels.
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.goal_yaw += dx * self.rotate_sensitivity;
+        self.goal_pitch = (self.goal_pitch - dy * self.rotate_sensitivity).clamp(-89.0, 89.0);
+    }
+
+    /// Slides the target in the view plane; `dx`/`dy` are mouse deltas in pixels.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let front = Self::front(self.goal_yaw, self.goal_pitch);
+        let right = front.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(front);
+        let scale = self.goal_distance * self.pan_sensitivity;
+        self.goal_target += (-right * dx + up * dy) * scale;
+    }
+
+    /// Moves towards (positive) or away from the target; `lines` is the scroll amount.
+    pub fn zoom(&mut self, lines: f32) {
+        self.goal_distance = (self.goal_distance * (1.0 - self.zoom_sensitivity).powf(lines))
+            .clamp(self.min_distance, self.max_distance);
+    }
+
+    /// Aims at the centre of `bounds` (world space) from a distance where the whole box fits
+    /// the camera's view, keeping the current viewing direction.
+    pub fn frame(&mut self, bounds: &Aabb, camera: &mut Camera) {
+        if bounds.is_empty() {
+            return;
+        }
+        let center = bounds.center();
+        let radius = bounds.radius().max(1e-3);
+        self.goal_target = Point3::new(center.x, center.y, center.z);
+
+        match &mut camera.projection {
+            Projection::Perspective { fov_y, .. } => {
+                let half_fov_y = Deg(*fov_y / 2.0);
+                let half_fov_x = Deg::atan(half_fov_y.tan() * camera.aspect);
+                let half_fov = if half_fov_x < half_fov_y { half_fov_x } else { half_fov_y };
+                self.goal_distance = radius / half_fov.sin();
+            }
+            Projection::Orthographic { half_height, .. } => {
+                *half_height = radius / camera.aspect.min(1.0);
+                self.goal_distance = radius * 2.0;
+            }
+        }
+        self.goal_distance = self.goal_distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    /// Eases the orbit towards its goal and places `camera` accordingly.
+    pub fn update(&mut self, camera: &mut Camera, delta_time: f32) {
+        let t = if self.damping > 0.0 { 1.0 - (-self.damping * delta_time).exp() } else { 1.0 };
+
+        self.target += (self.goal_target - self.target) * t;
+        self.distance += (self.goal_distance - self.distance) * t;
+        self.yaw += (self.goal_yaw - self.yaw) * t;
+        self.pitch += (self.goal_pitch - self.pitch) * t;
+
+        camera.position = self.target - Self::front(self.yaw, self.pitch) * self.distance;
+        camera.look_at(self.target);
+    }
+
+    /// Starts orbiting from wherever `camera` currently is, looking at `target`.
+    pub fn sync_from(&mut self, camera: &Camera, target: Point3<f32>) {
+        let offset = target - camera.position;
+        self.target = target;
+        self.distance = offset.magnitude().clamp(self.min_distance, self.max_distance);
+        let direction = offset.normalize();
+        self.yaw = direction.z.atan2(direction.x).to_degrees();
+        self.pitch = direction.y.clamp(-1.0, 1.0).asin().to_degrees().clamp(-89.0, 89.0);
+
+        self.goal_target = self.target;
+        self.goal_distance = self.distance;
+        self.goal_yaw = self.yaw;
+        self.goal_pitch = self.pitch;
+    }
+
+    fn front(yaw: f32, pitch: f32) -> Vector3<f32> {
+        Vector3::new(
+            Deg(yaw).cos() * Deg(pitch).cos(),
+            Deg(pitch).sin(),
+            Deg(yaw).sin() * Deg(pitch).cos(),
+        )
+        .normalize()
+    }
+}
@@ -0,0 +1,100 @@
+use std::ffi::CStr;
+use std::fs;
+use std::path::PathBuf;
+
+/// On-disk store of linked program binaries (`glGetProgramBinary`).
+///
+/// Entries are keyed by a hash of the shader sources together with the GL vendor, renderer
+/// and version strings, so a driver update or a different GPU simply misses the cache.
+pub struct ProgramCache {
+    pub dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// True when the current context can hand out program binaries at all.
+    pub fn is_supported() -> bool {
+        let mut formats = 0;
+        unsafe {
+            if !gl::GetProgramBinary::is_loaded()
+                || !gl::ProgramBinary::is_loaded()
+                || !gl::ProgramParameteri::is_loaded()
+            {
+                return false;
+            }
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        formats > 0
+    }
+
+    /// Cache key for a program built from `sources` on the current driver.
+    pub fn key(sources: &[&str]) -> u64 {
+        let mut hash = Fnv1a::new();
+        for source in sources {
+            hash.write(source.as_bytes());
+            hash.write(&[0]);
+        }
+        for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hash.write(gl_string(name).as_bytes());
+            hash.write(&[0]);
+        }
+        hash.0
+    }
+
+    /// Returns the binary format and the binary stored under `key`, if any.
+    pub fn load(&self, key: u64) -> Option<(u32, Vec<u8>)> {
+        let data = fs::read(self.path(key)).ok()?;
+        if data.len() < 4 {
+            return None;
+        }
+        let format = u32::from_le_bytes(data[..4].try_into().unwrap());
+        Some((format, data[4..].to_vec()))
+    }
+
+    pub fn store(&self, key: u64, format: u32, binary: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let mut data = Vec::with_capacity(4 + binary.len());
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(binary);
+        fs::write(self.path(key), data)
+    }
+
+    /// Drops an entry the driver refused, so it is rebuilt on the next run.
+    pub fn remove(&self, key: u64) {
+        let _ = fs::remove_file(self.path(key));
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+fn gl_string(name: gl::types::GLenum) -> String {
+    unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(ptr as *const _).to_string_lossy().into_owned()
+    }
+}
+
+// FNV-1a: unlike `DefaultHasher` its output is stable across Rust releases, which matters for
+// keys that outlive the process.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
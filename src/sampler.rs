@@ -0,0 +1,296 @@
+use crate::gl_object::GlSampler;
+
+use std::cell::Cell;
+use std::ffi::CStr;
+
+// GL 4.6 / GL_EXT_texture_filter_anisotropic, same values in both; the bindings stop at 4.5
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    /// Samples outside `0..1` read `SamplerDescriptor::border_color`.
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn to_gl(self) -> i32 {
+        (match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }) as i32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// Comparison used by depth textures sampled through `sampler2DShadow` and friends: the
+/// result is 1 where `reference <op> stored depth` holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+    Always,
+}
+
+impl CompareFunc {
+    fn to_gl(self) -> i32 {
+        (match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::Always => gl::ALWAYS,
+        }) as i32
+    }
+}
+
+/// How a texture is sampled. Applied either to a texture's own state (`apply_to_texture`)
+/// or to a `Sampler` object that overrides it on whatever unit the sampler is bound to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDescriptor {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Filter between mip levels; `None` samples the base level only.
+    pub mipmap_filter: Option<Filter>,
+    /// Maximum anisotropy, 1 for none. Clamped to what the driver supports, and ignored
+    /// where anisotropic filtering is not available.
+    pub anisotropy: f32,
+    pub border_color: [f32; 4],
+    /// Turns on depth comparison (shadow sampling) with the given function.
+    pub compare: Option<CompareFunc>,
+}
+
+impl SamplerDescriptor {
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn with_mipmap_filter(mut self, filter: Option<Filter>) -> Self {
+        self.mipmap_filter = filter;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn with_border_color(mut self, color: [f32; 4]) -> Self {
+        self.border_color = color;
+        self
+    }
+
+    pub fn with_compare(mut self, compare: Option<CompareFunc>) -> Self {
+        self.compare = compare;
+        self
+    }
+
+    /// Nearest filtering without mipmaps, clamped: for pixel art, lookup tables and
+    /// render targets read texel by texel.
+    pub fn nearest() -> Self {
+        Self::default()
+            .with_filter(Filter::Nearest)
+            .with_mipmap_filter(None)
+            .with_wrap(Wrap::ClampToEdge)
+    }
+
+    /// Linear depth comparison clamped to a white border, so lookups outside a shadow map
+    /// count as lit.
+    pub fn shadow() -> Self {
+        Self::default()
+            .with_mipmap_filter(None)
+            .with_wrap(Wrap::ClampToBorder)
+            .with_border_color([1.0; 4])
+            .with_compare(Some(CompareFunc::LessEqual))
+    }
+
+    /// Writes every setting into the state of the texture bound to `target`.
+    pub fn apply_to_texture(&self, target: u32) {
+        unsafe {
+            self.apply(
+                |name, value| gl::TexParameteri(target, name, value),
+                |name, value| gl::TexParameterf(target, name, value),
+                |name, value| gl::TexParameterfv(target, name, value.as_ptr()),
+            );
+        }
+    }
+
+    fn min_filter_gl(&self) -> i32 {
+        (match (self.min_filter, self.mipmap_filter) {
+            (Filter::Nearest, None) => gl::NEAREST,
+            (Filter::Linear, None) => gl::LINEAR,
+            (Filter::Nearest, Some(Filter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, Some(Filter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, Some(Filter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, Some(Filter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        }) as i32
+    }
+
+    /// Feeds every parameter to the setters, shared by textures and sampler objects.
+    fn apply(
+        &self,
+        mut set_int: impl FnMut(u32, i32),
+        mut set_float: impl FnMut(u32, f32),
+        mut set_color: impl FnMut(u32, &[f32; 4]),
+    ) {
+        set_int(gl::TEXTURE_WRAP_S, self.wrap_s.to_gl());
+        set_int(gl::TEXTURE_WRAP_T, self.wrap_t.to_gl());
+        set_int(gl::TEXTURE_WRAP_R, self.wrap_r.to_gl());
+        set_int(gl::TEXTURE_MIN_FILTER, self.min_filter_gl());
+        set_int(
+            gl::TEXTURE_MAG_FILTER,
+            (match self.mag_filter {
+                Filter::Nearest => gl::NEAREST,
+                Filter::Linear => gl::LINEAR,
+            }) as i32,
+        );
+        set_color(gl::TEXTURE_BORDER_COLOR, &self.border_color);
+
+        match self.compare {
+            Some(func) => {
+                set_int(gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+                set_int(gl::TEXTURE_COMPARE_FUNC, func.to_gl());
+            }
+            None => set_int(gl::TEXTURE_COMPARE_MODE, gl::NONE as i32),
+        }
+
+        let max = max_anisotropy();
+        if max > 1.0 {
+            set_float(TEXTURE_MAX_ANISOTROPY, self.anisotropy.clamp(1.0, max));
+        }
+    }
+}
+
+impl Default for SamplerDescriptor {
+    /// Trilinear, repeating, no anisotropy, transparent black border, no comparison.
+    fn default() -> Self {
+        Self {
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            wrap_r: Wrap::Repeat,
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: Some(Filter::Linear),
+            anisotropy: 1.0,
+            border_color: [0.0; 4],
+            compare: None,
+        }
+    }
+}
+
+/// GL sampler object: sampling state shared by any number of textures. While bound to a unit
+/// it overrides the sampling state of the texture bound there.
+pub struct Sampler {
+    object: GlSampler,
+    pub descriptor: SamplerDescriptor,
+}
+
+impl Sampler {
+    pub fn new(descriptor: &SamplerDescriptor) -> Self {
+        let object = GlSampler::new();
+        let id = object.id();
+        unsafe {
+            descriptor.apply(
+                |name, value| gl::SamplerParameteri(id, name, value),
+                |name, value| gl::SamplerParameterf(id, name, value),
+                |name, value| gl::SamplerParameterfv(id, name, value.as_ptr()),
+            );
+        }
+        Self { object, descriptor: *descriptor }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.object.id()
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id());
+        }
+    }
+
+    /// Goes back to the bound texture's own sampling state on `unit`.
+    pub fn unbind(unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+
+thread_local! {
+    // GL contexts are current on one thread, so the answer is cached per thread
+    static MAX_ANISOTROPY: Cell<Option<f32>> = const { Cell::new(None) };
+}
+
+/// Largest anisotropy the driver accepts, or 1 when anisotropic filtering is unavailable.
+/// Needs a current context.
+pub fn max_anisotropy() -> f32 {
+    MAX_ANISOTROPY.with(|cached| {
+        if let Some(max) = cached.get() {
+            return max;
+        }
+        let max = if has_anisotropic_filtering() {
+            let mut max = 1.0;
+            unsafe {
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max);
+            }
+            max
+        } else {
+            1.0
+        };
+        cached.set(Some(max));
+        max
+    })
+}
+
+fn has_anisotropic_filtering() -> bool {
+    unsafe {
+        let (mut major, mut minor) = (0, 0);
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        if (major, minor) >= (4, 6) {
+            return true;
+        }
+
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        (0..count as u32).any(|i| {
+            let name = gl::GetStringi(gl::EXTENSIONS, i);
+            !name.is_null()
+                && matches!(
+                    CStr::from_ptr(name as *const _).to_bytes(),
+                    b"GL_EXT_texture_filter_anisotropic" | b"GL_ARB_texture_filter_anisotropic"
+                )
+        })
+    }
+}
@@ -1,137 +1,297 @@
-use gl;
+use std::collections::HashMap;
 use std::ffi::CString;
-use std::fs; //fylesystem
 use std::ptr;
 
-use cgmath::{Matrix, Matrix4, Vector3, Array, Matrix3};
+use cgmath::{Matrix4, Vector3, Matrix3};
+
+use crate::gl_object::GlProgram;
+use crate::program_cache::ProgramCache;
+use crate::uniform::Uniform;
+use crate::uniform_buffer::UniformBlock;
+use crate::vfs;
 
 pub struct Shader {
-    pub id: gl::types::GLuint,
+    program: GlProgram,
 }
 
 impl Shader {
 
     pub fn new(vertex_path: &str, fragment_path: &str) -> Result<Self, String> {
-        
-        let vertex_code = fs::read_to_string(vertex_path)
+        Self::with_defines(vertex_path, fragment_path, &[])
+    }
+
+    /// Compiles both stages with `#define <name>` inserted after the `#version` line for every
+    /// entry in `defines`, e.g. the ones returned by `Material::defines`.
+    pub fn with_defines(vertex_path: &str, fragment_path: &str, defines: &[String]) -> Result<Self, String> {
+        let (vertex_code, fragment_code) = Self::read_sources(vertex_path, fragment_path)?;
+        let program = Self::compile(
+            &Self::insert_defines(&vertex_code, defines),
+            &Self::insert_defines(&fragment_code, defines),
+            false,
+        )?;
+        Ok(Shader {program})
+    }
+
+    /// Same as `new`, but reuses a linked program binary from `cache` when one exists for these
+    /// sources and this driver. A binary the driver rejects is discarded and the program is
+    /// compiled from source, then written back to the cache.
+    pub fn new_cached(vertex_path: &str, fragment_path: &str, cache: &ProgramCache) -> Result<Self, String> {
+        Self::with_defines_cached(vertex_path, fragment_path, &[], cache)
+    }
+
+    /// `with_defines` through `cache`. The defines are part of the sources the cache key is
+    /// computed from, so every variant gets an entry of its own.
+    pub fn with_defines_cached(
+        vertex_path: &str,
+        fragment_path: &str,
+        defines: &[String],
+        cache: &ProgramCache,
+    ) -> Result<Self, String> {
+        let (vertex_code, fragment_code) = Self::read_sources(vertex_path, fragment_path)?;
+        let vertex_code = Self::insert_defines(&vertex_code, defines);
+        let fragment_code = Self::insert_defines(&fragment_code, defines);
+
+        if !ProgramCache::is_supported() {
+            let program = Self::compile(&vertex_code, &fragment_code, false)?;
+            return Ok(Shader {program});
+        }
+
+        let key = ProgramCache::key(&[&vertex_code, &fragment_code]);
+
+        if let Some((format, binary)) = cache.load(key) {
+            let program = GlProgram::new();
+            unsafe {
+                gl::ProgramBinary(program.id(), format, binary.as_ptr() as *const _, binary.len() as i32);
+
+                let mut success = gl::FALSE as gl::types::GLint;
+                gl::GetProgramiv(program.id(), gl::LINK_STATUS, &mut success);
+                if success == gl::TRUE as gl::types::GLint {
+                    return Ok(Shader {program});
+                }
+            }
+            cache.remove(key);
+        }
+
+        let program = Self::compile(&vertex_code, &fragment_code, true)?;
+        let id = program.id();
+        unsafe {
+            let mut length = 0;
+            gl::GetProgramiv(id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+            if length > 0 {
+                let mut binary = vec![0u8; length as usize];
+                let mut format = 0;
+                gl::GetProgramBinary(id, length, &mut length, &mut format, binary.as_mut_ptr() as *mut _);
+                binary.truncate(length as usize);
+                // a cache that cannot be written only costs startup time next run
+                let _ = cache.store(key, format, &binary);
+            }
+        }
+        Ok(Shader {program})
+    }
+
+    fn read_sources(vertex_path: &str, fragment_path: &str) -> Result<(String, String), String> {
+        let vertex_code = vfs::read_to_string(vertex_path)
             .map_err(|e| format!("ERRO::SHADER::FILE_NOT_READ (Vertex): {}", e))?;
-        let fragment_code = fs::read_to_string(fragment_path)
+        let fragment_code = vfs::read_to_string(fragment_path)
             .map_err(|e| format!("ERRO::SHADER::FILE_NOT_READ (Fragment): {}", e))?;
+        Ok((vertex_code, fragment_code))
+    }
+
+    fn insert_defines(source: &str, defines: &[String]) -> String {
+        if defines.is_empty() {
+            return source.to_string();
+        }
+        let block: String = defines.iter().map(|d| format!("#define {}\n", d)).collect();
+        // #version has to stay the first statement of the file
+        match source.find("#version").and_then(|start| source[start..].find('\n').map(|end| start + end + 1)) {
+            Some(split) => format!("{}{}{}", &source[..split], block, &source[split..]),
+            None => format!("{}{}", block, source),
+        }
+    }
 
+    fn compile(vertex_code: &str, fragment_code: &str, retrievable: bool) -> Result<GlProgram, String> {
         let v_shader_code = CString::new(vertex_code.as_bytes()).unwrap();
         let f_shader_code = CString::new(fragment_code.as_bytes()).unwrap();
 
         unsafe {
 
-            let vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vertex, 1, &v_shader_code.as_ptr(), ptr::null());
-            gl::CompileShader(vertex);
-            Self::check_compile_errors(vertex, "VERTEX")?;
-
-            let fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fragment, 1, &f_shader_code.as_ptr(), ptr::null());
-            gl::CompileShader(fragment);
-            Self::check_compile_errors(fragment, "FRAGMENT")?;
+            let vertex = Self::compile_stage(gl::VERTEX_SHADER, &v_shader_code, "VERTEX")?;
+            let fragment = match Self::compile_stage(gl::FRAGMENT_SHADER, &f_shader_code, "FRAGMENT") {
+                Ok(fragment) => fragment,
+                Err(e) => {
+                    gl::DeleteShader(vertex);
+                    return Err(e);
+                }
+            };
 
-            let id = gl::CreateProgram();
+            let program = GlProgram::new();
+            let id = program.id();
+            if retrievable {
+                gl::ProgramParameteri(id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
             gl::AttachShader(id, vertex);
             gl::AttachShader(id, fragment);
             gl::LinkProgram(id);
-            Self::check_compile_errors(id, "PROGRAM")?;
+            let linked = Self::check_compile_errors(id, "PROGRAM");
 
+            // attached shaders are only flagged here and go away with the program
             gl::DeleteShader(vertex);
             gl::DeleteShader(fragment);
 
-            Ok(Shader {id})
+            linked.map(|_| program)
 
         }
     }
 
+    pub fn id(&self) -> u32 {
+        self.program.id()
+    }
+
     pub fn use_program(&self) {
         unsafe {
-            gl::UseProgram(self.id);
+            gl::UseProgram(self.id());
         }
     }
 
-    #[allow(dead_code)]
-    pub fn set_bool(&self, name: &str, value: bool) {
+    /// Sets uniform `name` of this program, which must be the one in use. Accepts anything
+    /// implementing `Uniform`: scalars, glam or cgmath vectors and matrices, arrays of those,
+    /// and textures bound with `Texture::at_unit`.
+    pub fn set(&self, name: &str, value: impl Uniform) {
         let c_name = CString::new(name).unwrap();
         unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, c_name.as_ptr()), value as i32);
+            value.set_uniform(gl::GetUniformLocation(self.id(), c_name.as_ptr()));
         }
     }
 
-    #[allow(dead_code)]
+    pub fn set_bool(&self, name: &str, value: bool) {
+        self.set(name, value);
+    }
+
     pub fn set_int(&self, name: &str, value: i32) {
-        let c_name = CString::new(name).unwrap();
-        unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, c_name.as_ptr()), value);
-        }
+        self.set(name, value);
     }
 
-    #[allow(dead_code)]
     pub fn set_float(&self, name: &str,  value: f32) {
-        let c_name = CString::new(name).unwrap();
-        unsafe {
-            gl::Uniform1f(gl::GetUniformLocation(self.id, c_name.as_ptr()), value);
-        }
+        self.set(name, value);
     }
 
     pub fn set_mat3(&self, name: &str, mat: &Matrix3<f32>) {
-
-        let c_name = CString::new(name).unwrap();
-        unsafe {
-            gl::UniformMatrix3fv(
-                gl::GetUniformLocation(self.id, c_name.as_ptr()),
-                1,
-                gl::FALSE,
-                mat.as_ptr(),
-            );
-        }
-
+        self.set(name, mat);
     }
 
     pub fn set_mat4(&self, name: &str, mat: &Matrix4<f32>) {
+        self.set(name, mat);
+    }
 
-        let c_name = CString::new(name).unwrap();
+    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) {
+        self.set(name, value);
+    }
+
+    /// Attaches the program's `T::NAME` block to `T::BINDING` and checks that the block size and
+    /// member offsets reported by the driver match the Rust layout of `T`.
+    pub fn bind_uniform_block<T: UniformBlock>(&self) -> Result<(), String> {
+        let c_name = CString::new(T::NAME).unwrap();
         unsafe {
-            gl::UniformMatrix4fv(
-                gl::GetUniformLocation(self.id, c_name.as_ptr()),
-                1,
-                gl::FALSE,
-                mat.as_ptr(), //cgmath já usa o mesmo layout de memória que o OpenGL espera
+            let index = gl::GetUniformBlockIndex(self.id(), c_name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return Err(format!("ERRO::UNIFORM_BLOCK_NOT_FOUND: {}", T::NAME));
+            }
+            gl::UniformBlockBinding(self.id(), index, T::BINDING);
+
+            let mut data_size = 0;
+            gl::GetActiveUniformBlockiv(self.id(), index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut data_size);
+            if data_size as usize != std::mem::size_of::<T>() {
+                return Err(format!(
+                    "ERRO::UNIFORM_BLOCK_SIZE_MISMATCH ({}): shader {} bytes, rust {} bytes",
+                    T::NAME, data_size, std::mem::size_of::<T>()
+                ));
+            }
+
+            let mut count = 0;
+            gl::GetActiveUniformBlockiv(self.id(), index, gl::UNIFORM_BLOCK_ACTIVE_UNIFORMS, &mut count);
+            let mut indices = vec![0i32; count as usize];
+            gl::GetActiveUniformBlockiv(
+                self.id(),
+                index,
+                gl::UNIFORM_BLOCK_ACTIVE_UNIFORM_INDICES,
+                indices.as_mut_ptr(),
             );
-       }
+
+            for uniform in indices {
+                let uniform = uniform as u32;
+                let mut name_buf = vec![0u8; 256];
+                let mut length = 0;
+                gl::GetActiveUniformName(
+                    self.id(),
+                    uniform,
+                    name_buf.len() as i32,
+                    &mut length,
+                    name_buf.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+                let full_name = String::from_utf8_lossy(&name_buf[..length as usize]).to_string();
+                // blocks with an instance name report members as "Block.member"
+                let name = full_name
+                    .strip_prefix(&format!("{}.", T::NAME))
+                    .unwrap_or(&full_name);
+
+                let mut offset = 0;
+                gl::GetActiveUniformsiv(self.id(), 1, &uniform, gl::UNIFORM_OFFSET, &mut offset);
+
+                match T::FIELDS.iter().find(|(field, _)| *field == name) {
+                    Some((_, expected)) if *expected == offset as usize => {}
+                    Some((_, expected)) => {
+                        return Err(format!(
+                            "ERRO::UNIFORM_BLOCK_OFFSET_MISMATCH ({}.{}): shader {}, rust {}",
+                            T::NAME, name, offset, expected
+                        ));
+                    }
+                    None => {
+                        return Err(format!(
+                            "ERRO::UNIFORM_BLOCK_UNKNOWN_MEMBER ({}.{})",
+                            T::NAME, name
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn set_vec3(&self, name: &str, value: &Vector3<f32>) {
-        let c_name = CString::new(name).unwrap();
+    /// Compiles one stage, deleting the shader object again if compilation fails.
+    unsafe fn compile_stage(kind: gl::types::GLenum, code: &CString, shader_type: &str) -> Result<u32, String> {
         unsafe {
-            gl::Uniform3fv(
-                gl::GetUniformLocation(self.id, c_name.as_ptr()),
-                1,
-                value.as_ptr(),
-            ); 
+            let shader = gl::CreateShader(kind);
+            gl::ShaderSource(shader, 1, &code.as_ptr(), ptr::null());
+            gl::CompileShader(shader);
+            if let Err(e) = Self::check_compile_errors(shader, shader_type) {
+                gl::DeleteShader(shader);
+                return Err(e);
+            }
+            Ok(shader)
         }
     }
 
     unsafe fn check_compile_errors(shader_id: u32, shader_type: &str) -> Result<(), String> {
         unsafe {
             let mut success = gl::FALSE as gl::types::GLint;
-            let mut info_log = Vec::with_capacity(1024);
-            info_log.set_len(1024 - 1); //subtrai 1 para o terminador nulo \0
+            let mut info_log = vec![0u8; 1024];
+            let mut length = 0;
 
             if shader_type != "PROGRAM" {
                 gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
                 if success != gl::TRUE as gl::types::GLint {
-                    gl::GetShaderInfoLog(shader_id, 1024, ptr::null_mut(), info_log.as_mut_ptr() as *mut gl::types::GLchar);
+                    gl::GetShaderInfoLog(shader_id, info_log.len() as i32, &mut length, info_log.as_mut_ptr() as *mut gl::types::GLchar);
+                    // o comprimento devolvido não conta o terminador nulo \0
+                    info_log.truncate(length as usize);
                     let error_message = String::from_utf8_lossy(&info_log).to_string();
                     return Err(format!("ERRO::SHADER_COMPILATION_ERROR ({}):\n{}", shader_type, error_message));
                 }
             } else {
                 gl::GetProgramiv(shader_id, gl::LINK_STATUS, &mut success);
                 if success != gl::TRUE as gl::types::GLint {
-                    gl::GetProgramInfoLog(shader_id, 1024, ptr::null_mut(), info_log.as_mut_ptr() as *mut gl::types::GLchar);
+                    gl::GetProgramInfoLog(shader_id, info_log.len() as i32, &mut length, info_log.as_mut_ptr() as *mut gl::types::GLchar);
+                    // o comprimento devolvido não conta o terminador nulo \0
+                    info_log.truncate(length as usize);
                     let error_message = String::from_utf8_lossy(&info_log).to_string();
                     return Err(format!("ERRO::PROGRAM_LINKING_ERROR ({}):\n{}", shader_type, error_message));
                 }
@@ -142,11 +302,42 @@ impl Shader {
 
 }
 
-impl Drop for Shader {
+/// Programs built from one pair of source files with different `#define` sets, compiled the
+/// first time each set is asked for.
+pub struct ShaderVariants {
+    pub vertex_path: String,
+    pub fragment_path: String,
+    variants: HashMap<Vec<String>, Shader>,
+    cache: Option<ProgramCache>,
+}
 
-    fn drop(&mut self) {
-        unsafe {
-            gl::DeleteProgram(self.id);
+impl ShaderVariants {
+
+    pub fn new(vertex_path: &str, fragment_path: &str) -> Self {
+        Self {
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            variants: HashMap::new(),
+            cache: None,
+        }
+    }
+
+    /// Compiles the variants through `cache` (see `Shader::new_cached`).
+    pub fn with_cache(mut self, cache: ProgramCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn get(&mut self, defines: &[String]) -> Result<&Shader, String> {
+        let mut key = defines.to_vec();
+        key.sort();
+        if !self.variants.contains_key(&key) {
+            let shader = match &self.cache {
+                Some(cache) => Shader::with_defines_cached(&self.vertex_path, &self.fragment_path, &key, cache)?,
+                None => Shader::with_defines(&self.vertex_path, &self.fragment_path, &key)?,
+            };
+            self.variants.insert(key.clone(), shader);
         }
+        Ok(&self.variants[&key])
     }
 }
@@ -0,0 +1,126 @@
+/// Fixed-rate simulation clock. Frame time goes into an accumulator that is drained in
+/// whole ticks of `dt()` seconds, so the simulation advances the same way at any frame rate;
+/// the leftover fraction of a tick is the interpolation `alpha` for rendering.
+///
+/// `advance` and `step` only say how many ticks are due; simulated time moves forward as the
+/// caller runs them, one `tick` at a time, so `time` is right inside every tick.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    tick_duration: f64,
+    accumulator: f64,
+    ticks: u64,
+    /// Upper bound on ticks run for one frame. After a long stall the excess time is dropped
+    /// instead of making the next frames slower still catching up.
+    pub max_ticks_per_frame: u32,
+}
+
+impl FixedTimestep {
+
+    /// `tick_rate` in ticks per second. Panics unless it is positive and finite.
+    pub fn new(tick_rate: f32) -> Self {
+        assert_valid_rate(tick_rate);
+        Self {
+            tick_duration: 1.0 / tick_rate as f64,
+            accumulator: 0.0,
+            ticks: 0,
+            max_ticks_per_frame: 8,
+        }
+    }
+
+    pub fn tick_rate(&self) -> f32 {
+        (1.0 / self.tick_duration) as f32
+    }
+
+    /// Changes the rate from the next tick on, keeping the pending fraction of a tick.
+    pub fn set_tick_rate(&mut self, tick_rate: f32) {
+        assert_valid_rate(tick_rate);
+        let fraction = self.accumulator / self.tick_duration;
+        self.tick_duration = 1.0 / tick_rate as f64;
+        self.accumulator = fraction * self.tick_duration;
+    }
+
+    /// Seconds simulated by one tick.
+    pub fn dt(&self) -> f32 {
+        self.tick_duration as f32
+    }
+
+    /// Ticks run since the clock started.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Simulated seconds, i.e. `ticks() * dt()` (ignoring rate changes).
+    pub fn time(&self) -> f32 {
+        (self.ticks as f64 * self.tick_duration) as f32
+    }
+
+    /// Adds `frame_time` seconds of real time and returns how many ticks to run now.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.max(0.0) as f64;
+        let mut ticks = (self.accumulator / self.tick_duration) as u64;
+        if ticks > self.max_ticks_per_frame as u64 {
+            ticks = self.max_ticks_per_frame as u64;
+            self.accumulator = ticks as f64 * self.tick_duration;
+        }
+        self.accumulator -= ticks as f64 * self.tick_duration;
+        ticks as u32
+    }
+
+    /// Runs exactly `ticks` ticks whatever the real time, for deterministic playback. The
+    /// accumulator is cleared, so `alpha` is 0 afterwards.
+    pub fn step(&mut self, ticks: u32) -> u32 {
+        self.accumulator = 0.0;
+        ticks
+    }
+
+    /// Counts one tick as run: call it after each of the ticks `advance` or `step` returned.
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// How far real time is between the last tick and the next, in `0..1`: render
+    /// `previous.lerp(current, alpha)` for motion that stays smooth between ticks.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_duration).clamp(0.0, 1.0) as f32
+    }
+}
+
+fn assert_valid_rate(tick_rate: f32) {
+    assert!(tick_rate > 0.0 && tick_rate.is_finite(), "tick rate must be positive, got {}", tick_rate);
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_moves_one_tick_at_a_time() {
+        let mut timestep = FixedTimestep::new(10.0);
+        assert_eq!(timestep.advance(0.35), 3);
+        assert_eq!(timestep.time(), 0.0);
+        for expected in [1, 2, 3] {
+            timestep.tick();
+            assert_eq!(timestep.ticks(), expected);
+        }
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(60.0);
+        assert_eq!(timestep.advance(10.0), timestep.max_ticks_per_frame);
+        assert_eq!(timestep.advance(0.0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "tick rate must be positive")]
+    fn zero_rate_is_rejected() {
+        FixedTimestep::new(0.0);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::model_loading::texture::Texture;
+use crate::sampler::Sampler;
+
+use std::slice;
+
+/// A value that can be uploaded to a uniform location of the currently bound program.
+///
+/// Implemented for scalars, glam and cgmath vectors/matrices, arrays/slices of those, and
+/// textures bound to a unit, so `Shader::set` works with either math library.
+pub trait Uniform {
+    fn set_uniform(&self, location: i32);
+}
+
+/// Element types GLSL also accepts in uniform arrays (`uniform vec3 offsets[4]`).
+pub trait UniformArray: Sized {
+    fn set_uniform_array(values: &[Self], location: i32);
+}
+
+impl<T: UniformArray, const N: usize> Uniform for [T; N] {
+    fn set_uniform(&self, location: i32) {
+        T::set_uniform_array(self, location);
+    }
+}
+
+impl<T: UniformArray> Uniform for [T] {
+    fn set_uniform(&self, location: i32) {
+        T::set_uniform_array(self, location);
+    }
+}
+
+impl<T: UniformArray> Uniform for Vec<T> {
+    fn set_uniform(&self, location: i32) {
+        T::set_uniform_array(self, location);
+    }
+}
+
+impl<U: Uniform + ?Sized> Uniform for &U {
+    fn set_uniform(&self, location: i32) {
+        (**self).set_uniform(location);
+    }
+}
+
+// `$ty` must be laid out as `$n` consecutive `$scalar`s, which holds for every glam and cgmath
+// type listed below (glam's SIMD `Vec3A`/`Mat3A` are deliberately left out).
+macro_rules! impl_uniform_vector {
+    ($gl_fn:ident, $scalar:ty, $n:literal: $($ty:ty),+) => {$(
+        const _: () = assert!(std::mem::size_of::<$ty>() == $n * std::mem::size_of::<$scalar>());
+
+        impl UniformArray for $ty {
+            fn set_uniform_array(values: &[Self], location: i32) {
+                unsafe {
+                    gl::$gl_fn(location, values.len() as i32, values.as_ptr() as *const $scalar);
+                }
+            }
+        }
+
+        impl Uniform for $ty {
+            fn set_uniform(&self, location: i32) {
+                Self::set_uniform_array(slice::from_ref(self), location);
+            }
+        }
+    )+};
+}
+
+macro_rules! impl_uniform_matrix {
+    ($gl_fn:ident, $n:literal: $($ty:ty),+) => {$(
+        const _: () = assert!(std::mem::size_of::<$ty>() == $n * $n * std::mem::size_of::<f32>());
+
+        impl UniformArray for $ty {
+            fn set_uniform_array(values: &[Self], location: i32) {
+                unsafe {
+                    // both libraries are column-major, like OpenGL
+                    gl::$gl_fn(location, values.len() as i32, gl::FALSE, values.as_ptr() as *const f32);
+                }
+            }
+        }
+
+        impl Uniform for $ty {
+            fn set_uniform(&self, location: i32) {
+                Self::set_uniform_array(slice::from_ref(self), location);
+            }
+        }
+    )+};
+}
+
+impl_uniform_vector!(Uniform1fv, f32, 1: f32);
+impl_uniform_vector!(Uniform1iv, i32, 1: i32);
+impl_uniform_vector!(Uniform1uiv, u32, 1: u32);
+
+impl_uniform_vector!(Uniform2fv, f32, 2: glam::Vec2, cgmath::Vector2<f32>, cgmath::Point2<f32>);
+impl_uniform_vector!(Uniform3fv, f32, 3: glam::Vec3, cgmath::Vector3<f32>, cgmath::Point3<f32>);
+impl_uniform_vector!(Uniform4fv, f32, 4: glam::Vec4, cgmath::Vector4<f32>);
+
+impl_uniform_vector!(Uniform2iv, i32, 2: glam::IVec2, cgmath::Vector2<i32>);
+impl_uniform_vector!(Uniform3iv, i32, 3: glam::IVec3, cgmath::Vector3<i32>);
+impl_uniform_vector!(Uniform4iv, i32, 4: glam::IVec4, cgmath::Vector4<i32>);
+
+impl_uniform_vector!(Uniform2uiv, u32, 2: glam::UVec2, cgmath::Vector2<u32>);
+impl_uniform_vector!(Uniform3uiv, u32, 3: glam::UVec3, cgmath::Vector3<u32>);
+impl_uniform_vector!(Uniform4uiv, u32, 4: glam::UVec4, cgmath::Vector4<u32>);
+
+impl_uniform_matrix!(UniformMatrix2fv, 2: glam::Mat2, cgmath::Matrix2<f32>);
+impl_uniform_matrix!(UniformMatrix3fv, 3: glam::Mat3, cgmath::Matrix3<f32>);
+impl_uniform_matrix!(UniformMatrix4fv, 4: glam::Mat4, cgmath::Matrix4<f32>);
+
+impl UniformArray for bool {
+    fn set_uniform_array(values: &[Self], location: i32) {
+        let values: Vec<i32> = values.iter().map(|v| *v as i32).collect();
+        i32::set_uniform_array(&values, location);
+    }
+}
+
+impl Uniform for bool {
+    fn set_uniform(&self, location: i32) {
+        (*self as i32).set_uniform(location);
+    }
+}
+
+/// A texture paired with the unit it should be bound to. Setting it as a uniform binds the
+/// texture to that unit, binds the sampler object (or clears the unit's sampler, so the
+/// texture's own state applies) and points the sampler uniform at the unit.
+pub struct BoundTexture<'a> {
+    pub texture: &'a Texture,
+    pub unit: u32,
+    pub sampler: Option<&'a Sampler>,
+}
+
+impl Texture {
+    pub fn at_unit(&self, unit: u32) -> BoundTexture<'_> {
+        BoundTexture { texture: self, unit, sampler: None }
+    }
+}
+
+impl<'a> BoundTexture<'a> {
+    pub fn with_sampler(mut self, sampler: Option<&'a Sampler>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+}
+
+impl Uniform for BoundTexture<'_> {
+    fn set_uniform(&self, location: i32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + self.unit);
+            gl::BindTexture(self.texture.target(), self.texture.id());
+        }
+        match self.sampler {
+            Some(sampler) => sampler.bind(self.unit),
+            None => Sampler::unbind(self.unit),
+        }
+        (self.unit as i32).set_uniform(location);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::gl_object::GlBuffer;
+
+use cgmath::{Matrix4, Point3, Vector3};
+use std::marker::PhantomData;
+use std::mem::{self, offset_of};
+use std::ptr;
+
+/// A Rust struct whose memory layout matches a GLSL `layout (std140)` uniform block.
+///
+/// `NAME` is the block name used in the shaders and `BINDING` the binding point shared by
+/// every program that declares the block. `FIELDS` lists each member with its byte offset
+/// so `Shader::bind_uniform_block` can check it against what the driver reports.
+pub trait UniformBlock: Copy {
+    const NAME: &'static str;
+    const BINDING: u32;
+    const FIELDS: &'static [(&'static str, usize)];
+}
+
+// std140: vec3 is aligned as vec4, so every vec3 member carries an explicit pad.
+// mat4 is stored as four vec4 columns, which is exactly cgmath's layout.
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CameraBlock {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    pub view_pos: [f32; 3],
+    _pad0: f32,
+}
+
+impl CameraBlock {
+    pub fn new(view: &Matrix4<f32>, projection: &Matrix4<f32>, view_pos: Point3<f32>) -> Self {
+        Self {
+            view: (*view).into(),
+            projection: (*projection).into(),
+            view_pos: view_pos.into(),
+            _pad0: 0.0,
+        }
+    }
+}
+
+impl UniformBlock for CameraBlock {
+    const NAME: &'static str = "Camera";
+    const BINDING: u32 = 0;
+    const FIELDS: &'static [(&'static str, usize)] = &[
+        ("view", offset_of!(CameraBlock, view)),
+        ("projection", offset_of!(CameraBlock, projection)),
+        ("viewPos", offset_of!(CameraBlock, view_pos)),
+    ];
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightBlock {
+    pub position: [f32; 3],
+    _pad0: f32,
+    pub ambient: [f32; 3],
+    _pad1: f32,
+    pub diffuse: [f32; 3],
+    _pad2: f32,
+    pub specular: [f32; 3],
+    _pad3: f32,
+}
+
+impl LightBlock {
+    pub fn new(
+        position: Vector3<f32>,
+        ambient: Vector3<f32>,
+        diffuse: Vector3<f32>,
+        specular: Vector3<f32>,
+    ) -> Self {
+        Self {
+            position: position.into(),
+            ambient: ambient.into(),
+            diffuse: diffuse.into(),
+            specular: specular.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl UniformBlock for LightBlock {
+    const NAME: &'static str = "Light";
+    const BINDING: u32 = 1;
+    const FIELDS: &'static [(&'static str, usize)] = &[
+        ("position", offset_of!(LightBlock, position)),
+        ("ambient", offset_of!(LightBlock, ambient)),
+        ("diffuse", offset_of!(LightBlock, diffuse)),
+        ("specular", offset_of!(LightBlock, specular)),
+    ];
+}
+
+// std140 rounds block sizes up to a multiple of vec4
+const _: () = assert!(mem::size_of::<CameraBlock>().is_multiple_of(16));
+const _: () = assert!(mem::size_of::<LightBlock>().is_multiple_of(16));
+
+/// GPU buffer holding one `T`, attached to `T::BINDING` for the lifetime of the buffer.
+pub struct UniformBuffer<T: UniformBlock> {
+    pub buffer: GlBuffer,
+    _block: PhantomData<T>,
+}
+
+impl<T: UniformBlock> UniformBuffer<T> {
+    pub fn new() -> Self {
+        let buffer = GlBuffer::new();
+        let id = buffer.id();
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                mem::size_of::<T>() as isize,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, T::BINDING, id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+
+        Self { buffer, _block: PhantomData }
+    }
+
+    /// Uploads the whole block. Meant to be called once per frame, before any draw that reads it.
+    pub fn update(&self, data: &T) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer.id());
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                mem::size_of::<T>() as isize,
+                data as *const T as *const _,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+}
+
+impl<T: UniformBlock> Default for UniformBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
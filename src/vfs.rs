@@ -0,0 +1,293 @@
+//! Virtual filesystem the loaders read through: a file comes from disk when it exists there,
+//! or else from the first mounted archive holding it.
+//!
+//! An archive (built with `cargo run --bin pack`) stores models, textures, shaders and text
+//! files under the relative paths the code asks for, e.g. `resources/shaders/basic_lighting.vs`,
+//! so a build shipping one works from any working directory. Loose files win so that edits
+//! during development, and the hot reloads they trigger, are never shadowed by a stale copy
+//! in the archive.
+//!
+//! Archive layout, little-endian:
+//!
+//! - header: `LWAPACK1`, u64 offset of the index, u32 number of files, u32 reserved (0);
+//! - the file contents, back to back;
+//! - the index: per file a u32 path length, the path (UTF-8, `/`-separated), u64 offset and
+//!   u64 size of its contents.
+
+use crate::model_loading::texture_data::Reader;
+
+use anyhow::Context;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+const MAGIC: &[u8; 8] = b"LWAPACK1";
+const HEADER_SIZE: usize = 24;
+
+// read from worker threads too, hence a lock rather than a thread local
+static MOUNTED: RwLock<Vec<Archive>> = RwLock::new(Vec::new());
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    offset: u64,
+    size: u64,
+}
+
+/// An archive opened for reading. Only the index is kept in memory; files are read from disk
+/// when asked for.
+pub struct Archive {
+    pub path: PathBuf,
+    file: Mutex<File>,
+    entries: HashMap<String, Entry>,
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut file = File::open(path).with_context(|| format!("Failed to open archive {}", path.display()))?;
+        let entries = Self::read_index(&mut file).with_context(|| format!("Failed to read archive {}", path.display()))?;
+        Ok(Self { path: path.to_path_buf(), file: Mutex::new(file), entries })
+    }
+
+    fn read_index(file: &mut File) -> anyhow::Result<HashMap<String, Entry>> {
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header).context("file truncated: no header")?;
+        let header = Reader { bytes: &header };
+        if header.slice(0, 8)? != MAGIC {
+            anyhow::bail!("not an archive (bad magic)");
+        }
+        let index_offset = header.u64(8)?;
+        let count = header.u32(16)?;
+
+        let mut index = Vec::new();
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_to_end(&mut index)?;
+        let index = Reader { bytes: &index };
+
+        // not sized from `count`: the header is not trusted before the index is read
+        let mut entries = HashMap::new();
+        let mut at = 0;
+        for _ in 0..count {
+            let length = index.u32(at)? as usize;
+            let name = std::str::from_utf8(index.slice(at + 4, length)?).context("file path is not UTF-8")?;
+            let entry = Entry { offset: index.u64(at + 4 + length)?, size: index.u64(at + 12 + length)? };
+            if entry.offset.checked_add(entry.size).is_none_or(|end| end > index_offset) {
+                anyhow::bail!("{} lies outside the file contents", name);
+            }
+            entries.insert(name.to_string(), entry);
+            at += 20 + length;
+        }
+        Ok(entries)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(&normalize(path))
+    }
+
+    /// Paths of every file in the archive, in no particular order.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// The contents of `path`, or `None` if the archive does not hold it.
+    pub fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>> {
+        let entry = *self.entries.get(&normalize(path))?;
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut bytes = vec![0; entry.size as usize];
+        Some(
+            file.seek(SeekFrom::Start(entry.offset))
+                .and_then(|_| file.read_exact(&mut bytes))
+                .map(|_| bytes),
+        )
+    }
+
+    /// Writes an archive to `out` holding, for each `(path, source)` of `files`, the contents
+    /// of the file `source` under `path`.
+    pub fn create(out: &Path, files: &[(String, PathBuf)]) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(
+            File::create(out).with_context(|| format!("Failed to create archive {}", out.display()))?,
+        );
+        writer.write_all(&[0; HEADER_SIZE])?;
+
+        let mut offset = HEADER_SIZE as u64;
+        let mut index = Vec::new();
+        for (path, source) in files {
+            let mut source_file =
+                File::open(source).with_context(|| format!("Failed to read {}", source.display()))?;
+            let size = io::copy(&mut source_file, &mut writer)
+                .with_context(|| format!("Failed to read {}", source.display()))?;
+
+            let path = normalize(path);
+            index.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            index.extend_from_slice(path.as_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&size.to_le_bytes());
+            offset += size;
+        }
+        writer.write_all(&index)?;
+
+        // the header points at the index, known only now
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(MAGIC)?;
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&(files.len() as u32).to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Adds `archive` to the ones searched, after those mounted before it.
+pub fn mount(archive: Archive) {
+    MOUNTED.write().unwrap_or_else(|poisoned| poisoned.into_inner()).push(archive);
+}
+
+/// Mounts the archive at `path` if there is one, looking next to the executable when a
+/// relative `path` is not found from the working directory. Returns whether one was mounted;
+/// only an archive that exists but cannot be read is an error.
+pub fn mount_if_present(path: &str) -> anyhow::Result<bool> {
+    let next_to_exe = std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(path)));
+    let found = std::iter::once(PathBuf::from(path))
+        .chain(next_to_exe)
+        .find(|candidate| candidate.is_file());
+
+    match found {
+        Some(found) => {
+            mount(Archive::open(found)?);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Unmounts every archive: reads go to the loose files again.
+pub fn unmount_all() {
+    MOUNTED.write().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+}
+
+/// The contents of `path`, from disk if the file is there or else from the mounted archives.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    if Path::new(path).is_file() {
+        return std::fs::read(path);
+    }
+    for archive in MOUNTED.read().unwrap_or_else(|poisoned| poisoned.into_inner()).iter() {
+        if let Some(result) = archive.read(path) {
+            return result;
+        }
+    }
+    std::fs::read(path)
+}
+
+pub fn read_to_string(path: &str) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Whether `read(path)` would find a file.
+pub fn exists(path: &str) -> bool {
+    Path::new(path).is_file() || in_archive(path)
+}
+
+/// Writes `contents` to the loose file `path`, which `read` then prefers to any archived copy.
+pub fn write(path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    std::fs::write(path, contents)
+}
+
+/// Whether a mounted archive holds `path`.
+pub fn in_archive(path: &str) -> bool {
+    MOUNTED
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .any(|archive| archive.contains(path))
+}
+
+/// The form paths are stored in: `/`-separated, without `.` and with `..` resolved where
+/// possible, e.g. `./a/b/../c\d` becomes `a/c/d`.
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("vfs_test_{}_{}", std::process::id(), name))
+    }
+
+    fn archive_from_bytes(name: &str, bytes: &[u8]) -> anyhow::Result<Archive> {
+        let path = temp_path(name);
+        std::fs::write(&path, bytes).unwrap();
+        let archive = Archive::open(&path);
+        let _ = std::fs::remove_file(&path);
+        archive
+    }
+
+    #[test]
+    fn normalize_resolves_separators_and_dots() {
+        assert_eq!(normalize("./a/b/../c\\d"), "a/c/d");
+        assert_eq!(normalize("a//b/./c/"), "a/b/c");
+        assert_eq!(normalize("../a/../../b"), "../../b");
+        assert_eq!(normalize(""), "");
+    }
+
+    #[test]
+    fn create_and_open_round_trip() {
+        let sources = [temp_path("first.txt"), temp_path("second.vs")];
+        std::fs::write(&sources[0], "first").unwrap();
+        std::fs::write(&sources[1], "second shader").unwrap();
+        let out = temp_path("round_trip.pak");
+        let files = [
+            ("resources/./first.txt".to_string(), sources[0].clone()),
+            ("resources/shaders/second.vs".to_string(), sources[1].clone()),
+        ];
+
+        let result = Archive::create(&out, &files).and_then(|_| Archive::open(&out));
+        for path in sources.iter().chain([&out]) {
+            let _ = std::fs::remove_file(path);
+        }
+        let archive = result.unwrap();
+
+        let mut paths: Vec<&str> = archive.paths().collect();
+        paths.sort();
+        assert_eq!(paths, ["resources/first.txt", "resources/shaders/second.vs"]);
+        assert!(archive.contains("resources\\shaders\\second.vs"));
+        assert_eq!(archive.read("resources/first.txt").unwrap().unwrap(), b"first");
+        assert_eq!(archive.read("./resources/shaders/second.vs").unwrap().unwrap(), b"second shader");
+        assert!(archive.read("resources/missing.txt").is_none());
+    }
+
+    #[test]
+    fn malformed_archives_are_errors() {
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut bad_magic = header.clone();
+        bad_magic[0] = b'X';
+        assert!(archive_from_bytes("bad_magic.pak", &bad_magic).is_err());
+        assert!(archive_from_bytes("short_header.pak", &header[..HEADER_SIZE - 1]).is_err());
+        // the header promises an index entry that is not there
+        assert!(archive_from_bytes("no_index.pak", &header).is_err());
+
+        let mut huge_count = header.clone();
+        huge_count[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(archive_from_bytes("huge_count.pak", &huge_count).is_err());
+    }
+}
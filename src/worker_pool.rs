@@ -0,0 +1,70 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job<R> = Box<dyn FnOnce() -> R + Send>;
+
+/// Background threads running CPU-only jobs (file reads, parsing, decoding). Results are
+/// collected with `try_recv` on the thread that owns the pool, which is where GL work on
+/// them has to happen.
+pub struct WorkerPool<R: Send + 'static> {
+    jobs: Option<Sender<Job<R>>>,
+    results: Receiver<R>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<R: Send + 'static> WorkerPool<R> {
+    pub fn new(threads: usize) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<Job<R>>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads.max(1))
+            .map(|i| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("asset-worker-{}", i))
+                    .spawn(move || loop {
+                        // the lock is only held while waiting for the next job
+                        let job = jobs.lock().unwrap().recv();
+                        let Ok(job) = job else { break };
+                        if results.send(job()).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Failed to spawn worker thread")
+            })
+            .collect();
+
+        Self { jobs: Some(job_sender), results, workers }
+    }
+
+    /// One thread per core, leaving one for the main thread, at most 4.
+    pub fn with_default_threads() -> Self {
+        let cores = thread::available_parallelism().map_or(2, |n| n.get());
+        Self::new((cores - 1).clamp(1, 4))
+    }
+
+    pub fn spawn(&self, job: impl FnOnce() -> R + Send + 'static) {
+        if let Some(jobs) = &self.jobs {
+            // only fails once every worker is gone, i.e. after a job panicked in all of them
+            let _ = jobs.send(Box::new(job));
+        }
+    }
+
+    /// A finished job's result, if any, without blocking.
+    pub fn try_recv(&self) -> Option<R> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl<R: Send + 'static> Drop for WorkerPool<R> {
+    fn drop(&mut self) {
+        // closing the job channel ends the worker loops once queued jobs are done
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}